@@ -0,0 +1,85 @@
+//! Throughput of `Rack::play` on a few representative patches, one block of
+//! `BLOCK` samples per iteration. Run with `cargo bench -p oscen`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use oscen::filters::*;
+use oscen::instruments::*;
+use oscen::operators::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+
+const SAMPLE_RATE: f32 = 44_100.0;
+const BLOCK: usize = 512;
+
+type Patch = fn(&mut Rack);
+
+fn play_block(rack: &mut Rack) {
+    for _ in 0..BLOCK {
+        black_box(rack.play(SAMPLE_RATE));
+    }
+}
+
+/// White noise through a single lowpass biquad.
+fn biquad(rack: &mut Rack) {
+    let noise = WhiteNoiseBuilder::new().seed(1).rack(rack);
+    BiquadBuilder::new(noise.tag(), FilterType::Lowpass)
+        .cutoff(1_000.0)
+        .rack(rack);
+}
+
+/// Eight voices of a modulator and carrier FM pair, mixed down.
+fn fm_voices(rack: &mut Rack) {
+    let carriers: Vec<Tag> = (0..8)
+        .map(|i| {
+            let hz = 110.0 * (1.0 + i as f32 / 8.0);
+            let modulator = FmOperatorBuilder::new()
+                .hz(hz)
+                .ratio(2.0)
+                .feedback(0.3)
+                .rack(rack);
+            FmOperatorBuilder::new()
+                .hz(hz)
+                .phase_mod(modulator.tag())
+                .level(0.125)
+                .rack(rack)
+                .tag()
+        })
+        .collect();
+    MixerBuilder::new(carriers).rack(rack);
+}
+
+/// Sixteen plucked wave guide strings, all sounding, mixed down.
+fn wave_guides(rack: &mut Rack) {
+    let burst = WhiteNoiseBuilder::new().seed(1).rack(rack);
+    let strings: Vec<_> = (0..16)
+        .map(|i| {
+            let hz = 110.0 * 2f32.powf(i as f32 / 12.0);
+            WaveGuideBuilder::new(burst.tag())
+                .hz_inv(1.0 / hz)
+                .rack(rack)
+        })
+        .collect();
+    MixerBuilder::new(strings.iter().map(|s| s.tag()).collect()).rack(rack);
+    for s in strings {
+        s.on(rack);
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rack");
+    group.throughput(Throughput::Elements(BLOCK as u64));
+    let patches: [(&str, Patch); 3] = [
+        ("biquad", biquad),
+        ("fm_voices", fm_voices),
+        ("wave_guides", wave_guides),
+    ];
+    for (name, patch) in patches {
+        let mut rack = Rack::default();
+        patch(&mut rack);
+        group.bench_function(name, |b| b.iter(|| play_block(&mut rack)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -0,0 +1,396 @@
+use crate::rack::*;
+use crate::{build, props, tag};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+#[cfg(feature = "fft")]
+pub use spectrum::*;
+
+#[cfg(feature = "fft")]
+mod spectrum {
+    use crate::rack::*;
+    use crate::tag;
+    use crate::utils::hann;
+    use parking_lot::Mutex;
+    use rustfft::{num_complex::Complex, Fft, FftPlanner};
+    use std::sync::Arc;
+
+    /// Shared access to the magnitude bins computed by a `SpectrumAnalyzer`,
+    /// e.g. for drawing from a UI thread.
+    #[derive(Clone)]
+    pub struct SpectrumHandle {
+        bins: Arc<Mutex<Vec<f32>>>,
+    }
+
+    impl SpectrumHandle {
+        /// The magnitudes of bins `0..=size / 2`, where bin `k` is centered on
+        /// `k * sample_rate / size` hz. A full scale sine has magnitude 1.
+        pub fn magnitudes(&self) -> Vec<f32> {
+            self.bins.lock().clone()
+        }
+    }
+
+    /// Passes its input through unchanged while computing the spectrum of the
+    /// last `size` samples (Hann windowed) every `hop` samples.
+    pub struct SpectrumAnalyzer {
+        tag: Tag,
+        wave: Tag,
+        hop: usize,
+        fft: Arc<dyn Fft<f32>>,
+        window: Vec<f32>,
+        scratch: Mutex<Vec<Complex<f32>>>,
+        bins: Arc<Mutex<Vec<f32>>>,
+    }
+
+    impl SpectrumAnalyzer {
+        pub fn new<T: Into<Tag>>(tag: T, wave: Tag, size: usize, hop: usize) -> Self {
+            let fft = FftPlanner::new().plan_fft_forward(size);
+            Self {
+                tag: tag.into(),
+                wave,
+                hop,
+                fft,
+                window: hann(size),
+                scratch: Mutex::new(vec![Complex::default(); size]),
+                bins: Arc::new(Mutex::new(vec![0.0; size / 2 + 1])),
+            }
+        }
+
+        pub fn handle(&self) -> SpectrumHandle {
+            SpectrumHandle {
+                bins: self.bins.clone(),
+            }
+        }
+
+        fn analyze(&self, buffer: &RingBuffer) {
+            let size = self.window.len();
+            let mut scratch = self.scratch.lock();
+            for (i, (c, w)) in scratch.iter_mut().zip(&self.window).enumerate() {
+                *c = Complex::new(buffer.get((size - 1 - i) as f32) * w, 0.0);
+            }
+            self.fft.process(&mut scratch);
+            let scale = 2.0 / self.window.iter().sum::<f32>();
+            // Never block the audio thread on a reader.
+            if let Some(mut bins) = self.bins.try_lock() {
+                for (b, c) in bins.iter_mut().zip(scratch.iter()) {
+                    *b = c.norm() * scale;
+                }
+            }
+        }
+    }
+
+    impl Signal for SpectrumAnalyzer {
+        tag!();
+        fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+            let x = rack.outputs[(self.wave, 0)];
+            rack.outputs[(self.tag, 0)] = x;
+            rack.buffers.buffers_mut(self.tag).push(x);
+            let count = rack.state[(self.tag, 0)] as usize + 1;
+            if count >= self.hop {
+                self.analyze(rack.buffers.buffers(self.tag));
+                rack.state[(self.tag, 0)] = 0.0;
+            } else {
+                rack.state[(self.tag, 0)] = count as f32;
+            }
+        }
+
+        fn reset(&self, _rack: &mut Rack) {
+            self.bins.lock().fill(0.0);
+        }
+
+        fn duplicate(&self) -> Option<Arc<dyn Signal + Send + Sync>> {
+            Some(Arc::new(SpectrumAnalyzer {
+                tag: self.tag,
+                wave: self.wave,
+                hop: self.hop,
+                fft: self.fft.clone(),
+                window: self.window.clone(),
+                scratch: Mutex::new(self.scratch.lock().clone()),
+                bins: Arc::new(Mutex::new(self.bins.lock().clone())),
+            }))
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct SpectrumAnalyzerBuilder {
+        wave: Tag,
+        size: usize,
+        hop: usize,
+    }
+
+    impl SpectrumAnalyzerBuilder {
+        pub fn new(wave: Tag) -> Self {
+            Self {
+                wave,
+                size: 1024,
+                hop: 512,
+            }
+        }
+
+        pub fn size(&mut self, value: usize) -> &mut Self {
+            self.size = value;
+            self
+        }
+
+        pub fn hop(&mut self, value: usize) -> &mut Self {
+            self.hop = value;
+            self
+        }
+
+        pub fn rack(&self, rack: &mut Rack) -> Arc<SpectrumAnalyzer> {
+            let n = rack.num_modules();
+            let sa = Arc::new(SpectrumAnalyzer::new(n, self.wave, self.size, self.hop));
+            rack.buffers
+                .set_buffer(sa.tag(), RingBuffer::new(0, vec![0.0; self.size]));
+            rack.push(sa.clone());
+            sa
+        }
+    }
+}
+
+/// When an `Oscilloscope` starts capturing a frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriggerMode {
+    /// Trigger on a rising zero crossing, or after a frame's worth of samples
+    /// without one so that the display never freezes.
+    Auto,
+    /// Trigger when the input rises through `level`.
+    Rising { level: f32 },
+    /// Trigger when the input falls through `level`.
+    Falling { level: f32 },
+    /// Trigger when the output of another module, e.g. an LFO or a note's
+    /// gate, rises through `level`. Locks the display to that module rather
+    /// than to the input.
+    External { source: Tag, level: f32 },
+}
+
+/// Shared access to an `Oscilloscope`, e.g. from a UI thread.
+#[derive(Clone)]
+pub struct OscilloscopeHandle {
+    frames: Arc<Mutex<Vec<Vec<f32>>>>,
+    mode: Arc<Mutex<TriggerMode>>,
+}
+
+impl OscilloscopeHandle {
+    /// The most recently captured frame of the first channel, starting at the
+    /// trigger point.
+    pub fn frame(&self) -> Vec<f32> {
+        self.frames.lock()[0].clone()
+    }
+
+    /// The most recently captured frame of every channel, all captured over
+    /// the same samples so they can be drawn on top of each other.
+    pub fn frames(&self) -> Vec<Vec<f32>> {
+        self.frames.lock().clone()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.frames.lock().len()
+    }
+
+    pub fn trigger_mode(&self) -> TriggerMode {
+        *self.mode.lock()
+    }
+
+    pub fn set_trigger_mode(&self, mode: TriggerMode) {
+        *self.mode.lock() = mode;
+    }
+}
+
+/// Passes its inputs through unchanged while capturing frames of `length`
+/// samples that start at a trigger point, so that a periodic waveform is drawn
+/// in the same place each time. After each frame the trigger is ignored for
+/// `holdoff` seconds. With several channels, e.g. a signal before and after a
+/// filter, the first channel is the one that triggers and output `i` is
+/// channel `i`.
+pub struct Oscilloscope {
+    tag: Tag,
+    waves: Vec<Tag>,
+    length: usize,
+    frames: Arc<Mutex<Vec<Vec<f32>>>>,
+    mode: Arc<Mutex<TriggerMode>>,
+}
+
+impl Oscilloscope {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, length: usize, mode: TriggerMode) -> Self {
+        Self::with_channels(tag, vec![wave], length, mode)
+    }
+
+    pub fn with_channels<T: Into<Tag>>(
+        tag: T,
+        waves: Vec<Tag>,
+        length: usize,
+        mode: TriggerMode,
+    ) -> Self {
+        assert!(
+            !waves.is_empty() && waves.len() <= MAX_OUTPUTS,
+            "Oscilloscope needs between 1 and {MAX_OUTPUTS} channels"
+        );
+        Self {
+            tag: tag.into(),
+            frames: Arc::new(Mutex::new(vec![vec![0.0; length]; waves.len()])),
+            waves,
+            length,
+            mode: Arc::new(Mutex::new(mode)),
+        }
+    }
+
+    props!(holdoff, set_holdoff, 0);
+
+    pub fn channels(&self) -> usize {
+        self.waves.len()
+    }
+
+    pub fn handle(&self) -> OscilloscopeHandle {
+        OscilloscopeHandle {
+            frames: self.frames.clone(),
+            mode: self.mode.clone(),
+        }
+    }
+
+    fn triggered(&self, rack: &Rack, prev: f32, x: f32) -> bool {
+        // Never block the audio thread on a reader.
+        let mode = match self.mode.try_lock() {
+            Some(mode) => *mode,
+            None => return false,
+        };
+        match mode {
+            TriggerMode::Auto => {
+                (prev < 0.0 && x >= 0.0) || rack.state[(self.tag, 3)] as usize >= self.length
+            }
+            TriggerMode::Rising { level } => prev < level && x >= level,
+            TriggerMode::Falling { level } => prev > level && x <= level,
+            TriggerMode::External { source, level } => {
+                rack.state[(self.tag, 4)] < level && rack.outputs[(source, 0)] >= level
+            }
+        }
+    }
+
+    /// Push one sample of every channel, the buffer holds them interleaved.
+    fn capture(&self, rack: &mut Rack) {
+        for wave in self.waves.iter() {
+            let x = rack.outputs[(*wave, 0)];
+            rack.buffers.buffers_mut(self.tag).push(x);
+        }
+    }
+
+    fn publish(&self, buffer: &RingBuffer) {
+        let n = self.waves.len();
+        if let Some(mut frames) = self.frames.try_lock() {
+            for (c, frame) in frames.iter_mut().enumerate() {
+                for (i, v) in frame.iter_mut().enumerate() {
+                    *v = buffer.get((n * (self.length - 1 - i) + n - 1 - c) as f32);
+                }
+            }
+        }
+    }
+}
+
+impl Signal for Oscilloscope {
+    tag!("holdoff");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        // state: 0 = samples captured (0 when idle), 1 = holdoff samples left,
+        // 2 = previous input, 3 = samples waited for a trigger, 4 = previous
+        // input of an external trigger.
+        for (i, wave) in self.waves.iter().enumerate() {
+            rack.outputs[(self.tag, i)] = rack.outputs[(*wave, 0)];
+        }
+        let x = rack.outputs[(self.waves[0], 0)];
+        let prev = rack.state[(self.tag, 2)];
+        rack.state[(self.tag, 2)] = x;
+        let captured = rack.state[(self.tag, 0)] as usize;
+        if captured > 0 {
+            self.capture(rack);
+            if captured + 1 == self.length {
+                self.publish(rack.buffers.buffers(self.tag));
+                rack.state[(self.tag, 0)] = 0.0;
+                rack.state[(self.tag, 1)] = self.holdoff(rack) * sample_rate;
+            } else {
+                rack.state[(self.tag, 0)] = (captured + 1) as f32;
+            }
+        } else if rack.state[(self.tag, 1)] > 0.0 {
+            rack.state[(self.tag, 1)] -= 1.0;
+        } else if self.triggered(rack, prev, x) {
+            self.capture(rack);
+            rack.state[(self.tag, 3)] = 0.0;
+            if self.length == 1 {
+                self.publish(rack.buffers.buffers(self.tag));
+            } else {
+                rack.state[(self.tag, 0)] = 1.0;
+            }
+        } else {
+            rack.state[(self.tag, 3)] += 1.0;
+        }
+        if let Some(TriggerMode::External { source, .. }) = self.mode.try_lock().map(|m| *m) {
+            rack.state[(self.tag, 4)] = rack.outputs[(source, 0)];
+        }
+    }
+
+    fn reset(&self, _rack: &mut Rack) {
+        self.frames.lock().iter_mut().for_each(|f| f.fill(0.0));
+    }
+
+    fn duplicate(&self) -> Option<Arc<dyn Signal + Send + Sync>> {
+        Some(Arc::new(Oscilloscope {
+            tag: self.tag,
+            waves: self.waves.clone(),
+            length: self.length,
+            frames: Arc::new(Mutex::new(self.frames.lock().clone())),
+            mode: Arc::new(Mutex::new(*self.mode.lock())),
+        }))
+    }
+}
+
+#[derive(Clone)]
+pub struct OscilloscopeBuilder {
+    waves: Vec<Tag>,
+    length: usize,
+    trigger_mode: TriggerMode,
+    holdoff: Control,
+}
+
+impl OscilloscopeBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            waves: vec![wave],
+            length: 512,
+            trigger_mode: TriggerMode::Auto,
+            holdoff: 0.0.into(),
+        }
+    }
+
+    pub fn length(&mut self, value: usize) -> &mut Self {
+        self.length = value.max(1);
+        self
+    }
+
+    /// Capture `waves` as further channels alongside the first.
+    pub fn channels(&mut self, waves: &[Tag]) -> &mut Self {
+        self.waves.truncate(1);
+        self.waves.extend_from_slice(waves);
+        self
+    }
+
+    pub fn trigger_mode(&mut self, value: TriggerMode) -> &mut Self {
+        self.trigger_mode = value;
+        self
+    }
+
+    build!(holdoff);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Oscilloscope> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.holdoff;
+        let scope = Arc::new(Oscilloscope::with_channels(
+            n,
+            self.waves.clone(),
+            self.length,
+            self.trigger_mode,
+        ));
+        let len = self.length * self.waves.len();
+        rack.buffers
+            .set_buffer(scope.tag(), RingBuffer::new(0, vec![0.0; len]));
+        rack.push(scope.clone());
+        scope
+    }
+}
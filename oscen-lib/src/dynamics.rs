@@ -0,0 +1,313 @@
+use crate::rack::*;
+use crate::{build, props, tag};
+use std::sync::Arc;
+
+/// One-pole smoothing coefficient for a time constant of `time` seconds.
+pub(crate) fn coefficient(time: f32, sample_rate: f32) -> f32 {
+    (-1.0 / (time * sample_rate)).exp()
+}
+
+/// Measures the level of its input. Output 0 is the peak level, which rises
+/// and falls with `attack` and `release` times in seconds, and output 1 is the
+/// RMS level over a sliding window of `window` samples.
+#[derive(Debug, Copy, Clone)]
+pub struct LevelMeter {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl LevelMeter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(attack, set_attack, 0);
+    props!(release, set_release, 1);
+
+    pub fn peak(&self, rack: &Rack) -> f32 {
+        rack.outputs[(self.tag, 0)]
+    }
+
+    pub fn rms(&self, rack: &Rack) -> f32 {
+        rack.outputs[(self.tag, 1)]
+    }
+}
+
+impl Signal for LevelMeter {
+    tag!("attack", "release");
+
+    fn output_names(&self) -> &'static [&'static str] {
+        &["peak", "rms"]
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let x = rack.outputs[(self.wave, 0)];
+        let level = x.abs();
+        let peak = rack.outputs[(self.tag, 0)];
+        let a = if level > peak {
+            coefficient(self.attack(rack), sample_rate)
+        } else {
+            coefficient(self.release(rack), sample_rate)
+        };
+        rack.outputs[(self.tag, 0)] = a * peak + (1.0 - a) * level;
+
+        // Running sum of the squared samples in the window.
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        let oldest = buffer.get_max_delay();
+        buffer.push(x * x);
+        let n = buffer.len() as f32;
+        let sum = (rack.state[(self.tag, 0)] + x * x - oldest).max(0.0);
+        rack.state[(self.tag, 0)] = sum;
+        rack.outputs[(self.tag, 1)] = (sum / n).sqrt();
+    }
+}
+
+#[derive(Clone)]
+pub struct LevelMeterBuilder {
+    wave: Tag,
+    window: usize,
+    attack: Control,
+    release: Control,
+}
+
+impl LevelMeterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            window: 2048,
+            attack: 0.001.into(),
+            release: 0.3.into(),
+        }
+    }
+
+    pub fn window(&mut self, value: usize) -> &mut Self {
+        self.window = value.max(1);
+        self
+    }
+
+    build!(attack);
+    build!(release);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<LevelMeter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.attack;
+        rack.controls[(n, 1)] = self.release;
+        let meter = Arc::new(LevelMeter::new(n, self.wave));
+        rack.buffers
+            .set_buffer(meter.tag(), RingBuffer::new(0, vec![0.0; self.window]));
+        rack.push(meter.clone());
+        meter
+    }
+}
+
+/// Noise gate. Silences its input while the input's level, followed with
+/// `attack` and `release` times in seconds, stays below `threshold`. Once the
+/// level falls below the threshold the gate is held open for `hold` seconds
+/// before closing, and the gain moves at the `attack` and `release` times so
+/// the gate opens and closes without clicks.
+#[derive(Debug, Copy, Clone)]
+pub struct NoiseGate {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl NoiseGate {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(threshold, set_threshold, 0);
+    props!(attack, set_attack, 1);
+    props!(release, set_release, 2);
+    props!(hold, set_hold, 3);
+}
+
+impl Signal for NoiseGate {
+    tag!("threshold", "attack", "release", "hold");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let x = rack.outputs[(self.wave, 0)];
+        let attack = coefficient(self.attack(rack), sample_rate);
+        let release = coefficient(self.release(rack), sample_rate);
+
+        let level = x.abs();
+        let env = rack.state[(self.tag, 0)];
+        let a = if level > env { attack } else { release };
+        let env = a * env + (1.0 - a) * level;
+        rack.state[(self.tag, 0)] = env;
+
+        // Samples left before the gate may close once the level is below the
+        // threshold.
+        let open = env >= self.threshold(rack);
+        let hold = if open {
+            self.hold(rack) * sample_rate
+        } else {
+            (rack.state[(self.tag, 2)] - 1.0).max(0.0)
+        };
+        rack.state[(self.tag, 2)] = hold;
+
+        let target = if open || hold > 0.0 { 1.0 } else { 0.0 };
+        let gain = rack.state[(self.tag, 1)];
+        let a = if target > gain { attack } else { release };
+        let gain = a * gain + (1.0 - a) * target;
+        rack.state[(self.tag, 1)] = gain;
+        rack.outputs[(self.tag, 0)] = x * gain;
+    }
+}
+
+#[derive(Clone)]
+pub struct NoiseGateBuilder {
+    wave: Tag,
+    threshold: Control,
+    attack: Control,
+    release: Control,
+    hold: Control,
+}
+
+impl NoiseGateBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            threshold: 0.01.into(),
+            attack: 0.001.into(),
+            release: 0.1.into(),
+            hold: 0.05.into(),
+        }
+    }
+
+    build!(threshold);
+    build!(attack);
+    build!(release);
+    build!(hold);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<NoiseGate> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.threshold;
+        rack.controls[(n, 1)] = self.attack;
+        rack.controls[(n, 2)] = self.release;
+        rack.controls[(n, 3)] = self.hold;
+        let gate = Arc::new(NoiseGate::new(n, self.wave));
+        rack.push(gate.clone());
+        gate
+    }
+}
+
+/// Brickwall limiter with lookahead. The input is delayed by `lookahead`
+/// samples, which lets the gain ramp down in a straight line ahead of a peak
+/// so the output never exceeds `threshold`. Once the peaks have passed the
+/// gain recovers with the `release` time in seconds.
+#[derive(Debug, Copy, Clone)]
+pub struct Limiter {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Limiter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(threshold, set_threshold, 0);
+    props!(release, set_release, 1);
+
+    /// The gain currently applied, 1 when the limiter is not reducing it.
+    pub fn gain(&self, rack: &Rack) -> f32 {
+        rack.state[(self.tag, 0)]
+    }
+
+    /// Whether the output has gone above 1 in magnitude since the last
+    /// `clear_clipped`, which can only happen with a `threshold` above 1.
+    pub fn clipped(&self, rack: &Rack) -> bool {
+        rack.outputs[(self.tag, 1)] > 0.0
+    }
+
+    pub fn clear_clipped(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 1)] = 0.0;
+    }
+}
+
+impl Signal for Limiter {
+    tag!("threshold", "release");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn output_names(&self) -> &'static [&'static str] {
+        &["out", "clipped"]
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let threshold = self.threshold(rack).abs();
+        let a = coefficient(self.release(rack), sample_rate);
+        let prev = rack.state[(self.tag, 0)];
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        buffer.push(rack.outputs[(self.wave, 0)]);
+        let lookahead = buffer.len() - 1;
+
+        // Recover towards unity, but never faster than a straight line to the
+        // gain each sample in the window will need when it is output.
+        let mut gain = a * prev + (1.0 - a);
+        for delay in 0..=lookahead {
+            let x = buffer.get(delay as f32).abs();
+            if x > threshold {
+                let needed = threshold / x;
+                let samples = (lookahead - delay + 1) as f32;
+                gain = gain.min(prev + (needed - prev) / samples);
+            }
+        }
+        rack.state[(self.tag, 0)] = gain;
+        let out = gain * buffer.get(lookahead as f32);
+        rack.outputs[(self.tag, 0)] = out;
+        if out.abs() > 1.0 {
+            rack.outputs[(self.tag, 1)] = 1.0;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LimiterBuilder {
+    wave: Tag,
+    lookahead: usize,
+    threshold: Control,
+    release: Control,
+}
+
+impl LimiterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            lookahead: 64,
+            threshold: 1.0.into(),
+            release: 0.1.into(),
+        }
+    }
+
+    /// How many samples the input is delayed by, and so how long the gain has
+    /// to ramp down ahead of a peak.
+    pub fn lookahead(&mut self, value: usize) -> &mut Self {
+        self.lookahead = value;
+        self
+    }
+
+    build!(threshold);
+    build!(release);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Limiter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.threshold;
+        rack.controls[(n, 1)] = self.release;
+        rack.state[(n, 0)] = 1.0;
+        let limiter = Arc::new(Limiter::new(n, self.wave));
+        rack.buffers.set_buffer(
+            limiter.tag(),
+            RingBuffer::new(0, vec![0.0; self.lookahead + 1]),
+        );
+        rack.push(limiter.clone());
+        limiter
+    }
+}
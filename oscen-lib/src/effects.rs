@@ -0,0 +1,415 @@
+use crate::dynamics::coefficient;
+use crate::filters::FilterType;
+use crate::rack::*;
+use crate::utils::zap_denormal;
+use crate::{build, props, tag};
+use std::f32::consts::TAU;
+use std::sync::Arc;
+
+/// Shortest and longest delay of the `Flanger` sweep in seconds.
+const FLANGER_MIN_DELAY: f32 = 0.0005;
+const FLANGER_MAX_DELAY: f32 = 0.005;
+
+/// Flanger. A sine LFO at `rate` Hz sweeps a short delay between 0.5 ms and
+/// up to 5 ms as `depth` goes from 0 to 1. `feedback` feeds the delayed signal
+/// back into the delay line for a resonant sweep and `mix` blends the dry (0)
+/// and delayed (1) signals.
+#[derive(Debug, Copy, Clone)]
+pub struct Flanger {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Flanger {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(rate, set_rate, 0);
+    props!(depth, set_depth, 1);
+    props!(feedback, set_feedback, 2);
+    props!(mix, set_mix, 3);
+}
+
+impl Signal for Flanger {
+    tag!("rate", "depth", "feedback", "mix");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let phase = rack.state[(self.tag, 0)];
+        let sweep = 0.5 - 0.5 * (phase * TAU).cos();
+        let ph = phase + self.rate(rack) / sample_rate;
+        rack.state[(self.tag, 0)] = ph - ph.floor();
+
+        let depth = self.depth(rack).clamp(0.0, 1.0);
+        let seconds = FLANGER_MIN_DELAY + (FLANGER_MAX_DELAY - FLANGER_MIN_DELAY) * depth * sweep;
+        // One sample of the delay passes as the input is pushed after the read.
+        let delay = (seconds * sample_rate - 1.0).max(0.0);
+        let delayed = rack
+            .buffers
+            .buffers(self.tag)
+            .get_interpolated_with_state(delay, &mut rack.state[(self.tag, 1)]);
+
+        let input = rack.outputs[(self.wave, 0)];
+        let feedback = self.feedback(rack);
+        rack.buffers
+            .buffers_mut(self.tag)
+            .push(zap_denormal(input + feedback * delayed));
+        let mix = self.mix(rack);
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * input + mix * delayed;
+    }
+}
+
+#[derive(Clone)]
+pub struct FlangerBuilder {
+    wave: Tag,
+    rate: Control,
+    depth: Control,
+    feedback: Control,
+    mix: Control,
+    interpolation: InterpolationMode,
+}
+
+impl FlangerBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            rate: 0.25.into(),
+            depth: 1.0.into(),
+            feedback: 0.5.into(),
+            mix: 0.5.into(),
+            interpolation: InterpolationMode::default(),
+        }
+    }
+
+    build!(rate);
+    build!(depth);
+    build!(feedback);
+    build!(mix);
+
+    /// How the delay line is read between samples. Linear is cheapest.
+    pub fn interpolation(&mut self, value: InterpolationMode) -> &mut Self {
+        self.interpolation = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Flanger> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rate;
+        rack.controls[(n, 1)] = self.depth;
+        rack.controls[(n, 2)] = self.feedback;
+        rack.controls[(n, 3)] = self.mix;
+        let flanger = Arc::new(Flanger::new(n, self.wave));
+        // Room for the longest sweep at sample rates up to 192 kHz.
+        rack.buffers.set_buffer(
+            flanger.tag(),
+            RingBuffer::new(0, vec![0.0; 1024]).with_interpolation(self.interpolation),
+        );
+        rack.push(flanger.clone());
+        flanger
+    }
+}
+
+/// Stereo tremolo. A sine LFO at `rate` Hz lowers the level of the input by
+/// up to `depth` (0 to 1). The right channel's LFO runs `stereo_phase` cycles
+/// ahead of the left: 0 gives a plain tremolo on both channels and 0.5 pans
+/// the signal from side to side. Outputs `LEFT` and `RIGHT`.
+#[derive(Debug, Copy, Clone)]
+pub struct Tremolo {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl Tremolo {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(rate, set_rate, 0);
+    props!(depth, set_depth, 1);
+    props!(stereo_phase, set_stereo_phase, 2);
+}
+
+impl Signal for Tremolo {
+    tag!("rate", "depth", "stereo_phase");
+    fn bypass(&self, rack: &mut Rack) {
+        let input = rack.outputs[(self.wave, 0)];
+        rack.outputs[(self.tag, LEFT)] = input;
+        rack.outputs[(self.tag, RIGHT)] = input;
+    }
+    fn output_names(&self) -> &'static [&'static str] {
+        &["left", "right"]
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let phase = rack.state[(self.tag, 0)];
+        let ph = phase + self.rate(rack) / sample_rate;
+        rack.state[(self.tag, 0)] = ph - ph.floor();
+
+        let depth = self.depth(rack).clamp(0.0, 1.0);
+        let gain = |p: f32| 1.0 - depth * (0.5 - 0.5 * (p * TAU).cos());
+        let input = rack.outputs[(self.wave, 0)];
+        rack.outputs[(self.tag, LEFT)] = input * gain(phase);
+        rack.outputs[(self.tag, RIGHT)] = input * gain(phase + self.stereo_phase(rack));
+    }
+}
+
+#[derive(Clone)]
+pub struct TremoloBuilder {
+    wave: Tag,
+    rate: Control,
+    depth: Control,
+    stereo_phase: Control,
+}
+
+impl TremoloBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            rate: 5.0.into(),
+            depth: 0.5.into(),
+            stereo_phase: 0.0.into(),
+        }
+    }
+
+    build!(rate);
+    build!(depth);
+    build!(stereo_phase);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Tremolo> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rate;
+        rack.controls[(n, 1)] = self.depth;
+        rack.controls[(n, 2)] = self.stereo_phase;
+        let tremolo = Arc::new(Tremolo::new(n, self.wave));
+        rack.push(tremolo.clone());
+        tremolo
+    }
+}
+
+/// Attack and release times in seconds of the `AutoWah` envelope follower.
+const AUTO_WAH_ATTACK: f32 = 0.002;
+const AUTO_WAH_RELEASE: f32 = 0.1;
+
+/// Auto-wah. An envelope follower tracks the level of the input and sweeps
+/// the cutoff of a resonant filter up from `base_cutoff` Hz by as much as
+/// `range` octaves. `sensitivity` scales the level before it is clamped to 1,
+/// so quieter playing can still open the filter fully, and `resonance` is the
+/// filter's Q. The filter is a bandpass unless set otherwise in the builder.
+#[derive(Debug, Copy, Clone)]
+pub struct AutoWah {
+    tag: Tag,
+    wave: Tag,
+    filter_type: FilterType,
+}
+
+impl AutoWah {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, filter_type: FilterType) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            filter_type,
+        }
+    }
+    props!(sensitivity, set_sensitivity, 0);
+    props!(base_cutoff, set_base_cutoff, 1);
+    props!(range, set_range, 2);
+    props!(resonance, set_resonance, 3);
+
+    pub fn filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+
+    /// The filter cutoff used for the last sample.
+    pub fn cutoff(&self, rack: &Rack) -> f32 {
+        rack.state[(self.tag, 3)]
+    }
+}
+
+impl Signal for AutoWah {
+    tag!("sensitivity", "base_cutoff", "range", "resonance");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = rack.outputs[(self.wave, 0)];
+        let level = x.abs();
+        let env = rack.state[(tag, 0)];
+        let a = if level > env {
+            coefficient(AUTO_WAH_ATTACK, sample_rate)
+        } else {
+            coefficient(AUTO_WAH_RELEASE, sample_rate)
+        };
+        let env = zap_denormal(a * env + (1.0 - a) * level);
+        rack.state[(tag, 0)] = env;
+
+        let sweep = (env * self.sensitivity(rack)).clamp(0.0, 1.0);
+        let cutoff = self.base_cutoff(rack) * (self.range(rack) * sweep).exp2();
+        rack.state[(tag, 3)] = cutoff;
+
+        let (b, a) = self
+            .filter_type
+            .coefficients(cutoff, self.resonance(rack), 0.0, sample_rate);
+        let y = b[0] * x + rack.state[(tag, 1)];
+        rack.state[(tag, 1)] = zap_denormal(b[1] * x - a[0] * y + rack.state[(tag, 2)]);
+        rack.state[(tag, 2)] = zap_denormal(b[2] * x - a[1] * y);
+        rack.outputs[(tag, 0)] = y;
+    }
+}
+
+#[derive(Clone)]
+pub struct AutoWahBuilder {
+    wave: Tag,
+    filter_type: FilterType,
+    sensitivity: Control,
+    base_cutoff: Control,
+    range: Control,
+    resonance: Control,
+}
+
+impl AutoWahBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            filter_type: FilterType::Bandpass,
+            sensitivity: 2.0.into(),
+            base_cutoff: 300.0.into(),
+            range: 3.0.into(),
+            resonance: 4.0.into(),
+        }
+    }
+
+    pub fn filter_type(&mut self, value: FilterType) -> &mut Self {
+        self.filter_type = value;
+        self
+    }
+
+    build!(sensitivity);
+    build!(base_cutoff);
+    build!(range);
+    build!(resonance);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<AutoWah> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.sensitivity;
+        rack.controls[(n, 1)] = self.base_cutoff;
+        rack.controls[(n, 2)] = self.range;
+        rack.controls[(n, 3)] = self.resonance;
+        let wah = Arc::new(AutoWah::new(n, self.wave, self.filter_type));
+        rack.push(wah.clone());
+        wah
+    }
+}
+
+/// Pitch shifter. Two taps read a delay line at a different rate than it is
+/// written, which shifts the pitch by `pitch` semitones. Each tap sweeps
+/// across a window of `window` samples and jumps back when it reaches the
+/// end, the taps are half a window apart and crossfade so that one is silent
+/// whenever the other jumps. Each jump slips the phase of the output a
+/// little, so the shifted pitch wavers, longer windows waver less but smear
+/// transients. `mix` blends the dry (0) and shifted (1) signals.
+#[derive(Debug, Copy, Clone)]
+pub struct PitchShifter {
+    tag: Tag,
+    wave: Tag,
+    window: usize,
+}
+
+impl PitchShifter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, window: usize) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            window,
+        }
+    }
+    props!(pitch, set_pitch, 0);
+    props!(mix, set_mix, 1);
+
+    pub fn window(&self) -> usize {
+        self.window
+    }
+}
+
+impl Signal for PitchShifter {
+    tag!("pitch", "mix");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let window = self.window as f32;
+        let ratio = (self.pitch(rack) / 12.0).exp2();
+        // The delay shrinks when shifting up and grows when shifting down.
+        let phase = rack.state[(self.tag, 0)];
+        let ph = phase + (1.0 - ratio) / window;
+        rack.state[(self.tag, 0)] = ph - ph.floor();
+
+        let buffer = rack.buffers.buffers(self.tag);
+        let mut shifted = 0.0;
+        for (i, p) in [phase, (phase + 0.5).fract()].into_iter().enumerate() {
+            let gain = 0.5 - 0.5 * (p * TAU).cos();
+            // Keep clear of the newest samples, which a cubic read needs.
+            let state = &mut rack.state[(self.tag, 1 + i)];
+            shifted += gain * buffer.get_interpolated_with_state(2.0 + p * window, state);
+        }
+
+        let input = rack.outputs[(self.wave, 0)];
+        rack.buffers.buffers_mut(self.tag).push(input);
+        let mix = self.mix(rack);
+        rack.outputs[(self.tag, 0)] = (1.0 - mix) * input + mix * shifted;
+    }
+}
+
+#[derive(Clone)]
+pub struct PitchShifterBuilder {
+    wave: Tag,
+    window: usize,
+    pitch: Control,
+    mix: Control,
+    interpolation: InterpolationMode,
+}
+
+impl PitchShifterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            window: 2048,
+            pitch: 0.0.into(),
+            mix: 1.0.into(),
+            interpolation: InterpolationMode::default(),
+        }
+    }
+
+    /// Length of the window each tap sweeps, in samples.
+    pub fn window(&mut self, value: usize) -> &mut Self {
+        self.window = value.max(1);
+        self
+    }
+
+    build!(pitch);
+    build!(mix);
+
+    /// How the delay line is read between samples. Linear is cheapest.
+    pub fn interpolation(&mut self, value: InterpolationMode) -> &mut Self {
+        self.interpolation = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<PitchShifter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.pitch;
+        rack.controls[(n, 1)] = self.mix;
+        let shifter = Arc::new(PitchShifter::new(n, self.wave, self.window));
+        rack.buffers.set_buffer(
+            shifter.tag(),
+            RingBuffer::new(0, vec![0.0; self.window + 4]).with_interpolation(self.interpolation),
+        );
+        rack.push(shifter.clone());
+        shifter
+    }
+}
@@ -1,14 +1,56 @@
 use crate::rack::*;
-use crate::utils::{interp, interp_inv};
+use crate::utils::{interp, interp_inv, zap_denormal};
 use crate::{build, props, tag};
 use std::sync::Arc;
 
+/// What an `Adsr` does once its decay stage ends while the gate is still on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum EnvelopeMode {
+    /// Hold at the sustain level until the gate is turned off.
+    #[default]
+    Adsr,
+    /// Go straight back into the attack stage, repeating attack and decay
+    /// until the gate is turned off. The attack restarts from the sustain
+    /// level, so a sustain of 0 gives a full AD loop, e.g. for rhythmic
+    /// modulation.
+    LoopingAd,
+}
+
+/// The stage an `Adsr` is in, published as output 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnvelopeStage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+impl EnvelopeStage {
+    fn from_output(value: f32) -> Self {
+        match value as u8 {
+            1 => EnvelopeStage::Attack,
+            2 => EnvelopeStage::Decay,
+            3 => EnvelopeStage::Sustain,
+            4 => EnvelopeStage::Release,
+            _ => EnvelopeStage::Idle,
+        }
+    }
+}
+
+/// Time constant in seconds with which an `Adsr` retriggered at a velocity
+/// too low to reach its current output settles down to the new peak.
+pub const RETRIGGER_TIME: f32 = 0.005;
+
+/// Attack, decay, sustain, release envelope. Output 0 is the level and output
+/// 1 the current `EnvelopeStage`, e.g. for drawing the envelope in a UI.
 #[derive(Copy, Clone, Debug)]
 pub struct Adsr {
     tag: Tag,
     ax: f32,
     dx: f32,
     rx: f32,
+    mode: EnvelopeMode,
 }
 
 impl Adsr {
@@ -18,13 +60,24 @@ impl Adsr {
             ax,
             dx,
             rx,
+            mode: EnvelopeMode::default(),
         }
     }
 
+    pub fn with_mode(mut self, mode: EnvelopeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> EnvelopeMode {
+        self.mode
+    }
+
     props!(attack, set_attack, 0);
     props!(decay, set_decay, 1);
     props!(sustain, set_sustain, 2);
     props!(release, set_release, 3);
+    props!(velocity_sensitivity, set_velocity_sensitivity, 5);
 
     pub fn triggered(&self, rack: &Rack) -> bool {
         let ctrl = rack.controls[(self.tag, 4)];
@@ -38,42 +91,150 @@ impl Adsr {
         rack.controls[(self.tag, 4)] = value.into();
     }
 
+    /// The level last written to output 0.
+    pub fn level(&self, rack: &Rack) -> f32 {
+        rack.outputs[(self.tag, 0)]
+    }
+
+    pub fn stage(&self, rack: &Rack) -> EnvelopeStage {
+        EnvelopeStage::from_output(rack.outputs[(self.tag, 1)])
+    }
+
     pub fn on(&self, rack: &mut Rack) {
+        self.on_with_velocity(rack, 1.0);
+    }
+
+    /// Trigger the envelope with a `velocity` from 0 to 1, e.g. a midi
+    /// velocity divided by 127. The output is scaled by
+    /// `1 - velocity_sensitivity * (1 - velocity)`, so a sensitivity of 0
+    /// ignores velocity and 1 makes the peak level equal to it.
+    /// A retrigger restarts the attack from the current output. If that is
+    /// above the new peak, the output settles down to it over `RETRIGGER_TIME`.
+    /// While a `fast_release` is fading out the trigger waits until it ends.
+    pub fn on_with_velocity(&self, rack: &mut Rack, velocity: f32) {
+        let velocity = velocity.clamp(0.0, 1.0);
+        if self.fast_releasing(rack) {
+            rack.state[(self.tag, 6)] = 1.0 + velocity;
+            return;
+        }
+        rack.state[(self.tag, 3)] = velocity;
         self.set_triggered(rack, true);
         rack.state[(self.tag, 1)] = 0.0;
-        let x = rack.state[(self.tag, 2)];
-        rack.state[(self.tag, 0)] = interp_inv(0.0, 1.0 - self.ax, 1.0, x);
+        let gain = self.gain(rack);
+        let x = if gain > 0.0 {
+            rack.outputs[(self.tag, 0)] / gain
+        } else {
+            rack.state[(self.tag, 2)]
+        };
+        rack.state[(self.tag, 7)] = (x - 1.0).max(0.0);
+        let a = self.attack(rack).max(0.005);
+        rack.state[(self.tag, 0)] = a * interp_inv(0.0, 1.0 - self.ax, 1.0, x.min(1.0));
     }
 
     pub fn off(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 6)] = 0.0;
         self.set_triggered(rack, false);
     }
+
+    /// Fade out in a straight line over `time` seconds whatever the `release`,
+    /// e.g. when the voice is stolen for another note. An `on` before the
+    /// fade ends retriggers the envelope from 0 once it has, so the new note
+    /// starts without a click.
+    pub fn fast_release(&self, rack: &mut Rack, time: f32) {
+        self.set_triggered(rack, false);
+        rack.state[(self.tag, 4)] = 1.0;
+        rack.state[(self.tag, 5)] = rack.state[(self.tag, 2)] / time.max(1e-4);
+        rack.state[(self.tag, 6)] = 0.0;
+    }
+
+    pub fn fast_releasing(&self, rack: &Rack) -> bool {
+        rack.state[(self.tag, 4)] > 0.0
+    }
+
+    fn gain(&self, rack: &Rack) -> f32 {
+        1.0 - self.velocity_sensitivity(rack) * (1.0 - rack.state[(self.tag, 3)])
+    }
+
+    /// Write the level in state 2 to output 0, scaled by the velocity and by
+    /// what is left of the excess of a retrigger in state 7.
+    fn write_level(&self, rack: &mut Rack, sample_rate: f32) {
+        let excess = rack.state[(self.tag, 7)];
+        if excess > 0.0 {
+            rack.state[(self.tag, 7)] =
+                zap_denormal(excess * (-1.0 / (RETRIGGER_TIME * sample_rate)).exp());
+        }
+        rack.outputs[(self.tag, 0)] = self.gain(rack) * (1.0 + excess) * rack.state[(self.tag, 2)];
+    }
 }
 
 impl Signal for Adsr {
-    tag!();
+    tag!(
+        "attack",
+        "decay",
+        "sustain",
+        "release",
+        "triggered",
+        "velocity_sensitivity"
+    );
+    fn output_names(&self) -> &'static [&'static str] {
+        &["level", "stage"]
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        if self.fast_releasing(rack) {
+            let level = rack.state[(self.tag, 2)] - rack.state[(self.tag, 5)] / sample_rate;
+            rack.state[(self.tag, 2)] = level.max(0.0);
+            rack.outputs[(self.tag, 1)] = EnvelopeStage::Release as u8 as f32;
+            self.write_level(rack, sample_rate);
+            if level <= 0.0 {
+                rack.state[(self.tag, 4)] = 0.0;
+                // Past the end of the release, so idle unless retriggered.
+                rack.state[(self.tag, 0)] = f32::MAX;
+                let pending = rack.state[(self.tag, 6)];
+                if pending > 0.0 {
+                    rack.state[(self.tag, 6)] = 0.0;
+                    self.on_with_velocity(rack, pending - 1.0);
+                }
+            }
+            return;
+        }
         let a = self.attack(rack).max(0.005);
         let d = self.decay(rack).max(0.005);
         let s = self.sustain(rack);
         let r = self.release(rack).max(0.005);
         let triggered = self.triggered(rack);
-        rack.state[(self.tag, 2)] = match (triggered, rack.state[(self.tag, 0)]) {
-            (_, t) if t < a => interp(0.0, 1.0 - self.ax, 1.0, t / a),
-            (_, t) if t < a + d => interp(1.0, s + self.dx * (1.0 - s), s, (t - a) / d),
+        let t = rack.state[(self.tag, 0)];
+        if triggered && self.mode == EnvelopeMode::LoopingAd && t >= a + d {
+            // Re-enter the attack at the point where it reaches the sustain level.
+            let start = a * interp_inv(0.0, 1.0 - self.ax, 1.0, s).clamp(0.0, 1.0);
+            rack.state[(self.tag, 0)] = start + (t - a - d) % (a + d - start);
+        }
+        let (level, stage) = match (triggered, rack.state[(self.tag, 0)]) {
+            (_, t) if t < a => (
+                interp(0.0, 1.0 - self.ax, 1.0, t / a),
+                EnvelopeStage::Attack,
+            ),
+            (_, t) if t < a + d => (
+                interp(1.0, s + self.dx * (1.0 - s), s, (t - a) / d),
+                EnvelopeStage::Decay,
+            ),
             (true, t) => {
                 rack.state[(self.tag, 1)] = t - a - d;
-                s
+                (s, EnvelopeStage::Sustain)
             }
-            (false, t) if t < a + d + r + rack.state[(self.tag, 1)] => interp(
-                s,
-                self.rx * s,
-                0.0,
-                t - a - d - rack.state[(self.tag, 1)] / r,
+            (false, t) if t < a + d + r + rack.state[(self.tag, 1)] => (
+                interp(
+                    s,
+                    self.rx * s,
+                    0.0,
+                    t - a - d - rack.state[(self.tag, 1)] / r,
+                ),
+                EnvelopeStage::Release,
             ),
-            (false, _) => 0.0,
+            (false, _) => (0.0, EnvelopeStage::Idle),
         };
-        rack.outputs[(self.tag, 0)] = rack.state[(self.tag, 2)];
+        rack.state[(self.tag, 2)] = level;
+        rack.outputs[(self.tag, 1)] = stage as u8 as f32;
+        self.write_level(rack, sample_rate);
         rack.state[(self.tag, 0)] += 1.0 / sample_rate;
     }
 }
@@ -83,11 +244,13 @@ pub struct AdsrBuilder {
     ax: f32,
     dx: f32,
     rx: f32,
+    mode: EnvelopeMode,
     attack: Control,
     decay: Control,
     sustain: Control,
     release: Control,
     triggered: Control,
+    velocity_sensitivity: Control,
 }
 
 impl Default for AdsrBuilder {
@@ -97,15 +260,18 @@ impl Default for AdsrBuilder {
         let sustain = 1.0.into();
         let release = 0.1.into();
         let triggered = false.into();
+        let velocity_sensitivity = 0.0.into();
         Self {
             ax: 0.5,
             dx: 0.5,
             rx: 0.5,
+            mode: EnvelopeMode::default(),
             attack,
             decay,
             sustain,
             release,
             triggered,
+            velocity_sensitivity,
         }
     }
 }
@@ -132,6 +298,7 @@ impl AdsrBuilder {
     build!(decay);
     build!(sustain);
     build!(release);
+    build!(velocity_sensitivity);
 
     pub fn ax(&mut self, value: f32) -> &mut Self {
         self.ax = value;
@@ -145,6 +312,10 @@ impl AdsrBuilder {
         self.rx = value;
         self
     }
+    pub fn mode(&mut self, value: EnvelopeMode) -> &mut Self {
+        self.mode = value;
+        self
+    }
     pub fn triggered(&mut self, t: bool) -> &mut Self {
         self.triggered = t.into();
         self
@@ -156,8 +327,182 @@ impl AdsrBuilder {
         rack.controls[(n, 2)] = self.sustain;
         rack.controls[(n, 3)] = self.release;
         rack.controls[(n, 4)] = self.triggered;
-        let adsr = Arc::new(Adsr::new(n, self.ax, self.dx, self.rx));
+        rack.controls[(n, 5)] = self.velocity_sensitivity;
+        rack.state[(n, 3)] = 1.0;
+        let adsr = Arc::new(Adsr::new(n, self.ax, self.dx, self.rx).with_mode(self.mode));
         rack.push(adsr.clone());
         adsr
     }
 }
+
+/// The end of one segment of a `BreakpointEnvelope`: the envelope moves to
+/// `level` over `time` seconds. `curve` (between 0 and 1) is where the
+/// segment is halfway through its time, as a fraction of the way to `level`.
+/// 0.5 is a straight line, lower values start slowly and higher values start
+/// quickly, as the `ax` of an `Adsr`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Breakpoint {
+    pub time: f32,
+    pub level: f32,
+    pub curve: f32,
+}
+
+impl Breakpoint {
+    pub fn new(time: f32, level: f32, curve: f32) -> Self {
+        Self { time, level, curve }
+    }
+
+    pub fn linear(time: f32, level: f32) -> Self {
+        Self::new(time, level, 0.5)
+    }
+}
+
+/// Multi-segment envelope, or function generator. When triggered it walks
+/// through its breakpoints from its current level and then holds the level
+/// of the last one. With a loop set to `(start, end)` breakpoint indices the
+/// envelope goes back to breakpoint `start` every time it reaches `end`,
+/// until `release` lets it carry on to the rest of the breakpoints.
+///
+/// It is triggered by `trigger` or when the `trigger` control rises through
+/// 0.5, e.g. patched to a `GateToTrigger`.
+#[derive(Clone, Debug)]
+pub struct BreakpointEnvelope {
+    tag: Tag,
+    points: Vec<Breakpoint>,
+    loop_points: Option<(usize, usize)>,
+}
+
+impl BreakpointEnvelope {
+    pub fn new<T: Into<Tag>>(
+        tag: T,
+        points: Vec<Breakpoint>,
+        loop_points: Option<(usize, usize)>,
+    ) -> Self {
+        if let Some((start, end)) = loop_points {
+            assert!(
+                start < end && end < points.len(),
+                "loop must run forwards between two breakpoints"
+            );
+        }
+        Self {
+            tag: tag.into(),
+            points,
+            loop_points,
+        }
+    }
+
+    props!(trigger_input, set_trigger_input, 0);
+
+    pub fn points(&self) -> &[Breakpoint] {
+        &self.points
+    }
+
+    pub fn loop_points(&self) -> Option<(usize, usize)> {
+        self.loop_points
+    }
+
+    pub fn trigger(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 5)] = 1.0;
+    }
+
+    /// Leave the loop the next time its end is reached.
+    pub fn release(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 6)] = 1.0;
+    }
+
+    /// Is the envelope still moving.
+    pub fn active(&self, rack: &Rack) -> bool {
+        (rack.state[(self.tag, 0)] as usize) < self.points.len()
+    }
+}
+
+impl Signal for BreakpointEnvelope {
+    tag!("trigger");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        // state: 0 = segment, 1 = seconds into the segment, 2 = level the
+        // segment started from, 3 = level, 4 = previous trigger input,
+        // 5 = trigger pending, 6 = released from the loop.
+        let tag = self.tag;
+        let input = self.trigger_input(rack);
+        let rising = rack.state[(tag, 4)] < 0.5 && input >= 0.5;
+        rack.state[(tag, 4)] = input;
+        if rising || rack.state[(tag, 5)] > 0.0 {
+            rack.state[(tag, 0)] = 0.0;
+            rack.state[(tag, 1)] = 0.0;
+            rack.state[(tag, 2)] = rack.state[(tag, 3)];
+            rack.state[(tag, 5)] = 0.0;
+            rack.state[(tag, 6)] = 0.0;
+        }
+
+        let mut segment = rack.state[(tag, 0)] as usize;
+        let mut t = rack.state[(tag, 1)];
+        let mut start = rack.state[(tag, 2)];
+        let mut level = rack.state[(tag, 3)];
+        // Bounded, so a loop of zero length segments cannot hang.
+        for _ in 0..=self.points.len() {
+            let Some(point) = self.points.get(segment) else {
+                break;
+            };
+            if t < point.time {
+                let curve = point.curve.clamp(0.01, 0.99);
+                let mid = start + curve * (point.level - start);
+                level = interp(start, mid, point.level, t / point.time);
+                break;
+            }
+            t -= point.time;
+            start = point.level;
+            level = point.level;
+            segment = match self.loop_points {
+                Some((loop_start, end)) if segment == end && rack.state[(tag, 6)] == 0.0 => {
+                    loop_start + 1
+                }
+                _ => segment + 1,
+            };
+        }
+
+        rack.state[(tag, 0)] = segment as f32;
+        rack.state[(tag, 1)] = t + 1.0 / sample_rate;
+        rack.state[(tag, 2)] = start;
+        rack.state[(tag, 3)] = level;
+        rack.outputs[(tag, 0)] = level;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BreakpointEnvelopeBuilder {
+    points: Vec<Breakpoint>,
+    loop_points: Option<(usize, usize)>,
+    trigger_input: Control,
+}
+
+impl BreakpointEnvelopeBuilder {
+    pub fn new(points: &[Breakpoint]) -> Self {
+        Self {
+            points: points.to_vec(),
+            loop_points: None,
+            trigger_input: 0.0.into(),
+        }
+    }
+
+    /// Loop back to breakpoint `start` on reaching breakpoint `end`.
+    pub fn loop_points(&mut self, start: usize, end: usize) -> &mut Self {
+        self.loop_points = Some((start, end));
+        self
+    }
+
+    build!(trigger_input);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<BreakpointEnvelope> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.trigger_input;
+        // Idle until triggered.
+        rack.state[(n, 0)] = self.points.len() as f32;
+        let env = Arc::new(BreakpointEnvelope::new(
+            n,
+            self.points.clone(),
+            self.loop_points,
+        ));
+        rack.push(env.clone());
+        env
+    }
+}
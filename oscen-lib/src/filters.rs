@@ -1,4 +1,6 @@
+use crate::operators::delay_len;
 use crate::rack::*;
+use crate::utils::zap_denormal;
 use crate::{build, props, tag};
 use std::f32::consts::PI;
 use std::sync::Arc;
@@ -28,7 +30,10 @@ impl Lpf {
 }
 
 impl Signal for Lpf {
-    tag!();
+    tag!("cutoff", "q", "off");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let x0 = rack.outputs[(self.wave, 0)];
         let cut_off = self.cutoff(rack);
@@ -115,7 +120,10 @@ impl Hpf {
 }
 
 impl Signal for Hpf {
-    tag!();
+    tag!("cutoff", "q", "off");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let x0 = rack.outputs[(self.wave, 0)];
         let cut_off = self.cutoff(rack);
@@ -201,7 +209,10 @@ impl Bpf {
 }
 
 impl Signal for Bpf {
-    tag!();
+    tag!("cutoff", "q", "off");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let x0 = rack.outputs[(self.wave, 0)];
         let cut_off = self.cutoff(rack);
@@ -288,7 +299,10 @@ impl Notch {
 }
 
 impl Signal for Notch {
-    tag!();
+    tag!("cutoff", "q", "off");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let x0 = rack.outputs[(self.wave, 0)];
         let cut_off = self.cutoff(rack);
@@ -349,7 +363,8 @@ impl NotchBuilder {
         notch
     }
 }
-/// Lowpass-Feedback Comb Filter
+/// Lowpass-Feedback Comb Filter with a fixed length in samples, so its tuning
+/// changes with the sample rate. See `CombFilter` for a delay in seconds.
 // https://ccrma.stanford.edu/~jos/pasp/Lowpass_Feedback_Comb_Filter.html
 #[derive(Clone)]
 pub struct Comb {
@@ -370,7 +385,10 @@ impl Comb {
 }
 
 impl Signal for Comb {
-    tag!();
+    tag!("feedback", "dampening", "dampening_inverse");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         rack.outputs[(self.tag, 0)] = rack.buffers.buffers(self.tag).get_max_delay();
         rack.state[(self.tag, 0)] = rack.outputs[(self.tag, 0)] * self.dampening_inverse(rack)
@@ -419,6 +437,8 @@ impl CombBuilder {
     }
 }
 
+/// Freeverb's allpass, with a fixed length in samples and a feedback of 0.5.
+/// See `AllpassFilter` for a delay in seconds.
 #[derive(Debug, Copy, Clone)]
 pub struct AllPass {
     tag: Tag,
@@ -436,6 +456,9 @@ impl AllPass {
 
 impl Signal for AllPass {
     tag!();
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let input = rack.outputs[(self.wave, 0)];
         let delayed = rack.buffers.buffers(self.tag).get_max_delay();
@@ -456,11 +479,699 @@ impl AllPassBuilder {
     pub fn new(wave: Tag, length: usize) -> Self {
         Self { wave, length }
     }
-    pub fn rack(&mut self, rack: &mut Rack, buffers: &mut Buffers) -> Arc<AllPass> {
+    pub fn rack(&mut self, rack: &mut Rack) -> Arc<AllPass> {
         let n = rack.num_modules();
         let allpass = Arc::new(AllPass::new(n, self.wave));
-        buffers.set_buffer(allpass.tag, RingBuffer::new(1, vec![0.0; self.length]));
+        rack.buffers
+            .set_buffer(allpass.tag, RingBuffer::new(1, vec![0.0; self.length]));
+        rack.push(allpass.clone());
+        allpass
+    }
+}
+
+/// Feedback comb filter with a variable `delay` time in seconds, `feedback`
+/// gain and a one-pole lowpass in the feedback path set by `damping` (0 is no
+/// damping). A building block for reverbs, flangers and physical models.
+/// Unlike `Comb` the delay follows the sample rate, up to `max_delay`.
+#[derive(Debug, Copy, Clone)]
+pub struct CombFilter {
+    tag: Tag,
+    wave: Tag,
+    max_delay: f32,
+}
+
+impl CombFilter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            max_delay: 1.0,
+        }
+    }
+
+    /// The longest `delay` in seconds the buffer holds, 1 by default.
+    pub fn with_max_delay(mut self, seconds: f32) -> Self {
+        self.max_delay = seconds;
+        self
+    }
+
+    props!(delay, set_delay, 0);
+    props!(feedback, set_feedback, 1);
+    props!(damping, set_damping, 2);
+}
+
+/// State slot holding the previous output of an allpass interpolated read.
+const ALLPASS_STATE: usize = 1;
+
+/// Read the buffer `delay` seconds back from the sample about to be pushed,
+/// using the buffer's `InterpolationMode`.
+fn read_delayed(rack: &mut Rack, tag: Tag, delay: f32, sample_rate: f32) -> f32 {
+    let buffer = rack.buffers.buffers(tag);
+    let d = (delay * sample_rate).clamp(1.0, buffer.len() as f32 - 3.0);
+    buffer.get_interpolated_with_state(d - 1.0, &mut rack.state[(tag, ALLPASS_STATE)])
+}
+
+impl Signal for CombFilter {
+    tag!("delay", "feedback", "damping");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let delayed = read_delayed(rack, self.tag, self.delay(rack), sample_rate);
+        let damping = self.damping(rack);
+        let filtered =
+            zap_denormal(delayed * (1.0 - damping) + rack.state[(self.tag, 0)] * damping);
+        rack.state[(self.tag, 0)] = filtered;
+        let input = rack.outputs[(self.wave, 0)];
+        let feedback = self.feedback(rack);
+        rack.buffers
+            .buffers_mut(self.tag)
+            .push(zap_denormal(input + filtered * feedback));
+        rack.outputs[(self.tag, 0)] = delayed;
+    }
+    fn set_sample_rate(&self, rack: &mut Rack, sample_rate: f32) {
+        rack.buffers
+            .buffers_mut(self.tag)
+            .resize(delay_len(self.max_delay, sample_rate));
+    }
+}
+
+#[derive(Clone)]
+pub struct CombFilterBuilder {
+    wave: Tag,
+    delay: Control,
+    feedback: Control,
+    damping: Control,
+    interpolation: InterpolationMode,
+    max_delay: f32,
+}
+
+impl CombFilterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            delay: 0.03.into(),
+            feedback: 0.5.into(),
+            damping: 0.0.into(),
+            interpolation: InterpolationMode::default(),
+            max_delay: 1.0,
+        }
+    }
+
+    build!(delay);
+    build!(feedback);
+    build!(damping);
+
+    /// How the delay line is read between samples. `Allpass` keeps a tuned
+    /// comb, e.g. for Karplus-Strong, exactly in pitch.
+    pub fn interpolation(&mut self, value: InterpolationMode) -> &mut Self {
+        self.interpolation = value;
+        self
+    }
+
+    /// The longest `delay` in seconds, 1 by default.
+    pub fn max_delay(&mut self, value: f32) -> &mut Self {
+        self.max_delay = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<CombFilter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.delay;
+        rack.controls[(n, 1)] = self.feedback;
+        rack.controls[(n, 2)] = self.damping;
+        let comb = Arc::new(CombFilter::new(n, self.wave).with_max_delay(self.max_delay));
+        rack.buffers.set_buffer(
+            comb.tag,
+            RingBuffer::new(0, vec![0.0; delay_len(self.max_delay, 44_100.0)])
+                .with_interpolation(self.interpolation),
+        );
+        rack.push(comb.clone());
+        comb
+    }
+}
+
+/// Schroeder allpass filter with a variable `delay` time in seconds and
+/// `feedback` gain. Passes all frequencies at equal gain but smears their
+/// phase, a building block for reverb diffusion. Unlike `AllPass` the delay
+/// follows the sample rate, up to `max_delay`, and it is a true allpass.
+#[derive(Debug, Copy, Clone)]
+pub struct AllpassFilter {
+    tag: Tag,
+    wave: Tag,
+    max_delay: f32,
+}
+
+impl AllpassFilter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            max_delay: 1.0,
+        }
+    }
+
+    /// The longest `delay` in seconds the buffer holds, 1 by default.
+    pub fn with_max_delay(mut self, seconds: f32) -> Self {
+        self.max_delay = seconds;
+        self
+    }
+
+    props!(delay, set_delay, 0);
+    props!(feedback, set_feedback, 1);
+}
+
+impl Signal for AllpassFilter {
+    tag!("delay", "feedback");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let delayed = read_delayed(rack, self.tag, self.delay(rack), sample_rate);
+        let g = self.feedback(rack);
+        let v = zap_denormal(rack.outputs[(self.wave, 0)] + g * delayed);
+        rack.buffers.buffers_mut(self.tag).push(v);
+        rack.outputs[(self.tag, 0)] = delayed - g * v;
+    }
+    fn set_sample_rate(&self, rack: &mut Rack, sample_rate: f32) {
+        rack.buffers
+            .buffers_mut(self.tag)
+            .resize(delay_len(self.max_delay, sample_rate));
+    }
+}
+
+#[derive(Clone)]
+pub struct AllpassFilterBuilder {
+    wave: Tag,
+    delay: Control,
+    feedback: Control,
+    interpolation: InterpolationMode,
+    max_delay: f32,
+}
+
+impl AllpassFilterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            delay: 0.005.into(),
+            feedback: 0.5.into(),
+            interpolation: InterpolationMode::default(),
+            max_delay: 1.0,
+        }
+    }
+
+    build!(delay);
+    build!(feedback);
+
+    /// How the delay line is read between samples.
+    pub fn interpolation(&mut self, value: InterpolationMode) -> &mut Self {
+        self.interpolation = value;
+        self
+    }
+
+    /// The longest `delay` in seconds, 1 by default.
+    pub fn max_delay(&mut self, value: f32) -> &mut Self {
+        self.max_delay = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<AllpassFilter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.delay;
+        rack.controls[(n, 1)] = self.feedback;
+        let allpass = Arc::new(AllpassFilter::new(n, self.wave).with_max_delay(self.max_delay));
+        rack.buffers.set_buffer(
+            allpass.tag,
+            RingBuffer::new(0, vec![0.0; delay_len(self.max_delay, 44_100.0)])
+                .with_interpolation(self.interpolation),
+        );
         rack.push(allpass.clone());
         allpass
     }
 }
+
+/// Limits the rate of change of its input to `rise` units per second when the
+/// input is increasing and `fall` units per second when it is decreasing.
+/// Unlike an exponential (one-pole) smoother, which approaches its target ever
+/// more slowly, a slew limiter moves in a straight line at a fixed slope and
+/// arrives in a finite amount of time. Useful for portamento and for taming
+/// stepped modulation.
+#[derive(Debug, Copy, Clone)]
+pub struct SlewLimiter {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl SlewLimiter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(rise, set_rise, 0);
+    props!(fall, set_fall, 1);
+}
+
+impl Signal for SlewLimiter {
+    tag!("rise", "fall");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let input = rack.outputs[(self.wave, 0)];
+        let current = rack.state[(tag, 0)];
+        let max_rise = self.rise(rack).max(0.0) / sample_rate;
+        let max_fall = self.fall(rack).max(0.0) / sample_rate;
+        let out = current + (input - current).clamp(-max_fall, max_rise);
+        rack.state[(tag, 0)] = out;
+        rack.outputs[(tag, 0)] = out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct SlewLimiterBuilder {
+    wave: Tag,
+    rise: Control,
+    fall: Control,
+}
+
+impl SlewLimiterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            rise: 1.0.into(),
+            fall: 1.0.into(),
+        }
+    }
+
+    build!(rise);
+    build!(fall);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<SlewLimiter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.rise;
+        rack.controls[(n, 1)] = self.fall;
+        let slew = Arc::new(SlewLimiter::new(n, self.wave));
+        rack.push(slew.clone());
+        slew
+    }
+}
+
+/// Response of a `OnePole`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OnePoleMode {
+    #[default]
+    Lowpass,
+    /// E.g. a DC blocker with a low cutoff.
+    Highpass,
+}
+
+/// One-pole filter with a 6 dB per octave slope above (lowpass) or below
+/// (highpass) `cutoff` Hz. The lowpass smooths controls as well as audio.
+#[derive(Debug, Copy, Clone)]
+pub struct OnePole {
+    tag: Tag,
+    wave: Tag,
+    mode: OnePoleMode,
+}
+
+impl OnePole {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, mode: OnePoleMode) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            mode,
+        }
+    }
+    props!(cutoff, set_cutoff, 0);
+
+    pub fn mode(&self) -> OnePoleMode {
+        self.mode
+    }
+}
+
+impl Signal for OnePole {
+    tag!("cutoff");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let x = rack.outputs[(self.wave, 0)];
+        let a = (-2.0 * PI * self.cutoff(rack).max(0.0) / sample_rate).exp();
+        let y = rack.state[(tag, 0)];
+        let y = match self.mode {
+            OnePoleMode::Lowpass => (1.0 - a) * x + a * y,
+            // Kept apart from the lowpass, where `x - lowpass` would leave a
+            // residue of DC from the rounding of a slow lowpass.
+            OnePoleMode::Highpass => {
+                let x1 = rack.state[(tag, 1)];
+                rack.state[(tag, 1)] = x;
+                a * (y + (x - x1))
+            }
+        };
+        let y = zap_denormal(y);
+        rack.state[(tag, 0)] = y;
+        rack.outputs[(tag, 0)] = y;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct OnePoleBuilder {
+    wave: Tag,
+    mode: OnePoleMode,
+    cutoff: Control,
+}
+
+impl OnePoleBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            mode: OnePoleMode::Lowpass,
+            cutoff: 1_000.0.into(),
+        }
+    }
+
+    pub fn mode(&mut self, value: OnePoleMode) -> &mut Self {
+        self.mode = value;
+        self
+    }
+
+    build!(cutoff);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<OnePole> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cutoff;
+        let one_pole = Arc::new(OnePole::new(n, self.wave, self.mode));
+        rack.push(one_pole.clone());
+        one_pole
+    }
+}
+
+/// Response of a `Biquad`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FilterType {
+    #[default]
+    Lowpass,
+    Highpass,
+    /// Constant 0 dB gain at the center frequency.
+    Bandpass,
+    Notch,
+    /// Boost or cut of `gain_db` around the center frequency.
+    Peaking,
+    /// Boost or cut of `gain_db` below the cutoff.
+    LowShelf,
+    /// Boost or cut of `gain_db` above the cutoff.
+    HighShelf,
+}
+
+impl FilterType {
+    /// Biquad coefficients `([b0, b1, b2], [a1, a2])` normalized by `a0`, from
+    /// Robert Bristow-Johnson's Audio EQ Cookbook.
+    pub fn coefficients(
+        self,
+        cutoff: f32,
+        q: f32,
+        gain_db: f32,
+        sample_rate: f32,
+    ) -> ([f32; 3], [f32; 2]) {
+        let cutoff = cutoff.max(1.0).min(0.49 * sample_rate);
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q.max(1e-3));
+        let a = 10f32.powf(gain_db / 40.0);
+        let shelf = 2.0 * a.sqrt() * alpha;
+        let (b, a) = match self {
+            FilterType::Lowpass => (
+                [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::Highpass => (
+                [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::Bandpass => ([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha]),
+            FilterType::Notch => (
+                [1.0, -2.0 * cos, 1.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::Peaking => (
+                [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+                [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+            ),
+            FilterType::LowShelf => (
+                [
+                    a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                    a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                ],
+                [
+                    (a + 1.0) + (a - 1.0) * cos + shelf,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                    (a + 1.0) + (a - 1.0) * cos - shelf,
+                ],
+            ),
+            FilterType::HighShelf => (
+                [
+                    a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                    a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                ],
+                [
+                    (a + 1.0) - (a - 1.0) * cos + shelf,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                    (a + 1.0) - (a - 1.0) * cos - shelf,
+                ],
+            ),
+        };
+        (
+            [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            [a[1] / a[0], a[2] / a[0]],
+        )
+    }
+}
+
+/// Note frequency at which keytracking leaves the cutoff unchanged, middle C.
+pub const KEYTRACK_REFERENCE: f32 = 261.63;
+
+/// Biquad filter with a selectable `FilterType`, processed in transposed
+/// direct form II. `gain_db` is only used by the peaking and shelf types.
+/// Patch the frequency of the played note into `keytrack_hz` and the cutoff
+/// becomes `cutoff * (keytrack_hz / KEYTRACK_REFERENCE) ^ keytrack_amount`, so
+/// with an amount of 1 the filter opens an octave for every octave played.
+///
+/// The coefficients are recomputed from the controls every sample rather
+/// than every few frames, so the cutoff can be modulated at audio rate
+/// without zipper noise.
+#[derive(Debug, Copy, Clone)]
+pub struct Biquad {
+    tag: Tag,
+    wave: Tag,
+    filter_type: FilterType,
+}
+
+impl Biquad {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag, filter_type: FilterType) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            filter_type,
+        }
+    }
+    props!(cutoff, set_cutoff, 0);
+    props!(q, set_q, 1);
+    props!(gain_db, set_gain_db, 2);
+    props!(keytrack_hz, set_keytrack_hz, 3);
+    props!(keytrack_amount, set_keytrack_amount, 4);
+
+    pub fn filter_type(&self) -> FilterType {
+        self.filter_type
+    }
+}
+
+impl Signal for Biquad {
+    tag!("cutoff", "q", "gain_db", "keytrack_hz", "keytrack_amount");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let mut cutoff = self.cutoff(rack);
+        let amount = self.keytrack_amount(rack);
+        let key = self.keytrack_hz(rack);
+        if amount != 0.0 && key > 0.0 {
+            cutoff *= (key / KEYTRACK_REFERENCE).powf(amount);
+        }
+        let (b, a) =
+            self.filter_type
+                .coefficients(cutoff, self.q(rack), self.gain_db(rack), sample_rate);
+        let x = rack.outputs[(self.wave, 0)];
+        let y = b[0] * x + rack.state[(tag, 0)];
+        rack.state[(tag, 0)] = zap_denormal(b[1] * x - a[0] * y + rack.state[(tag, 1)]);
+        rack.state[(tag, 1)] = zap_denormal(b[2] * x - a[1] * y);
+        rack.outputs[(tag, 0)] = y;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BiquadBuilder {
+    wave: Tag,
+    filter_type: FilterType,
+    cutoff: Control,
+    q: Control,
+    gain_db: Control,
+    keytrack_hz: Control,
+    keytrack_amount: Control,
+}
+
+impl BiquadBuilder {
+    pub fn new(wave: Tag, filter_type: FilterType) -> Self {
+        Self {
+            wave,
+            filter_type,
+            cutoff: 1_000.0.into(),
+            q: 0.707.into(),
+            gain_db: 0.0.into(),
+            keytrack_hz: KEYTRACK_REFERENCE.into(),
+            keytrack_amount: 0.0.into(),
+        }
+    }
+
+    build!(cutoff);
+    build!(q);
+    build!(gain_db);
+    build!(keytrack_hz);
+    build!(keytrack_amount);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Biquad> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.cutoff;
+        rack.controls[(n, 1)] = self.q;
+        rack.controls[(n, 2)] = self.gain_db;
+        rack.controls[(n, 3)] = self.keytrack_hz;
+        rack.controls[(n, 4)] = self.keytrack_amount;
+        let biquad = Arc::new(Biquad::new(n, self.wave, self.filter_type));
+        rack.push(biquad.clone());
+        biquad
+    }
+}
+
+/// Formants of a bass voice singing a, e, i, o and u: center frequency in Hz,
+/// gain in dB and bandwidth in Hz of each of the five formants.
+const FORMANTS: [[(f32, f32, f32); 5]; 5] = [
+    [
+        (600.0, 0.0, 60.0),
+        (1040.0, -7.0, 70.0),
+        (2250.0, -9.0, 110.0),
+        (2450.0, -9.0, 120.0),
+        (2750.0, -20.0, 130.0),
+    ],
+    [
+        (400.0, 0.0, 40.0),
+        (1620.0, -12.0, 80.0),
+        (2400.0, -9.0, 100.0),
+        (2800.0, -12.0, 120.0),
+        (3100.0, -18.0, 120.0),
+    ],
+    [
+        (250.0, 0.0, 60.0),
+        (1750.0, -30.0, 90.0),
+        (2600.0, -16.0, 100.0),
+        (3050.0, -22.0, 120.0),
+        (3340.0, -28.0, 120.0),
+    ],
+    [
+        (400.0, 0.0, 40.0),
+        (750.0, -11.0, 80.0),
+        (2400.0, -21.0, 100.0),
+        (2600.0, -20.0, 120.0),
+        (2900.0, -40.0, 120.0),
+    ],
+    [
+        (350.0, 0.0, 40.0),
+        (600.0, -20.0, 80.0),
+        (2400.0, -32.0, 100.0),
+        (2675.0, -28.0, 120.0),
+        (2950.0, -36.0, 120.0),
+    ],
+];
+
+/// Vowel filter made of five bandpass filters in parallel, one per formant.
+/// `vowel` morphs through a, e, i, o and u as it goes from 0 to 4 and
+/// `resonance` scales the Q of every formant, 1 being the Q of the table.
+#[derive(Debug, Copy, Clone)]
+pub struct FormantFilter {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl FormantFilter {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(vowel, set_vowel, 0);
+    props!(resonance, set_resonance, 1);
+}
+
+impl Signal for FormantFilter {
+    tag!("vowel", "resonance");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let vowel = self.vowel(rack).clamp(0.0, 4.0);
+        let i = (vowel as usize).min(3);
+        let f = vowel - i as f32;
+        let resonance = self.resonance(rack);
+        let x = rack.outputs[(self.wave, 0)];
+        let mut out = 0.0;
+        for (k, (from, to)) in FORMANTS[i].iter().zip(FORMANTS[i + 1]).enumerate() {
+            let lerp = |a: f32, b: f32| a + (b - a) * f;
+            let hz = lerp(from.0, to.0);
+            let gain = lerp(10f32.powf(from.1 / 20.0), 10f32.powf(to.1 / 20.0));
+            let q = hz / lerp(from.2, to.2) * resonance;
+            let (b, a) = FilterType::Bandpass.coefficients(hz, q, 0.0, sample_rate);
+            let (s1, s2) = (2 * k, 2 * k + 1);
+            let y = b[0] * x + rack.state[(tag, s1)];
+            rack.state[(tag, s1)] = zap_denormal(b[1] * x - a[0] * y + rack.state[(tag, s2)]);
+            rack.state[(tag, s2)] = zap_denormal(b[2] * x - a[1] * y);
+            out += gain * y;
+        }
+        rack.outputs[(tag, 0)] = out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct FormantFilterBuilder {
+    wave: Tag,
+    vowel: Control,
+    resonance: Control,
+}
+
+impl FormantFilterBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            vowel: 0.0.into(),
+            resonance: 1.0.into(),
+        }
+    }
+
+    build!(vowel);
+    build!(resonance);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<FormantFilter> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.vowel;
+        rack.controls[(n, 1)] = self.resonance;
+        let formant = Arc::new(FormantFilter::new(n, self.wave));
+        rack.push(formant.clone());
+        formant
+    }
+}
@@ -1,13 +1,21 @@
 use crate::{build, props, tag};
-use crate::{envelopes::*, filters::LpfBuilder, operators::*, rack::*};
+use crate::{envelopes::*, filters::LpfBuilder, operators::*, oscillators::*, rack::*};
 use std::sync::Arc;
 
+/// Karplus-Strong string. The `burst` excites a delay line of `hz_inv`
+/// seconds with a lowpass filter at `cutoff` and gain `decay` in its feedback
+/// loop. A noise transient, e.g. a hammer or pluck, whose level is set by
+/// `transient` and scaled by the note velocity, can be mixed over the string.
+/// The modules making the transient are only built when the builder is given
+/// a `transient`, without one the control does nothing.
 #[derive(Clone)]
 pub struct WaveGuide {
     tag: Tag,
     _burst: Tag,
     adsr: Arc<Adsr>,
     mixer: Arc<Mixer>,
+    transient_adsr: Option<Arc<Adsr>>,
+    transient_source: Option<Tag>,
 }
 
 impl WaveGuide {
@@ -17,18 +25,50 @@ impl WaveGuide {
             _burst: burst,
             adsr,
             mixer,
+            transient_adsr: None,
+            transient_source: None,
         }
     }
+
+    /// Mix `source`, e.g. the noise burst of a hammer shaped by `adsr`, over
+    /// the string at the level of the `transient` control.
+    pub fn with_transient(mut self, adsr: Arc<Adsr>, source: Tag) -> Self {
+        self.transient_adsr = Some(adsr);
+        self.transient_source = Some(source);
+        self
+    }
     props!(hz_inv, set_hz_inv, 0);
     props!(cutoff, set_cutoff, 1);
     props!(decay, set_decay, 2);
+    props!(transient, set_transient, 3);
 
     pub fn on(&self, rack: &mut Rack) {
+        self.on_with_velocity(rack, 1.0);
+    }
+
+    /// Pluck the string with a `velocity` from 0 to 1, which scales the level
+    /// of the noise transient.
+    pub fn on_with_velocity(&self, rack: &mut Rack, velocity: f32) {
         self.adsr.on(rack);
+        if let Some(adsr) = &self.transient_adsr {
+            adsr.on_with_velocity(rack, velocity);
+        }
     }
 
     pub fn off(&self, rack: &mut Rack) {
         self.adsr.off(rack);
+        if let Some(adsr) = &self.transient_adsr {
+            adsr.off(rack);
+        }
+    }
+
+    /// Fade the excitation out over `time` seconds, see `Adsr::fast_release`.
+    /// The string itself rings on as set by `decay`.
+    pub fn fast_release(&self, rack: &mut Rack, time: f32) {
+        self.adsr.fast_release(rack, time);
+        if let Some(adsr) = &self.transient_adsr {
+            adsr.fast_release(rack, time);
+        }
     }
 
     pub fn set_adsr_attack(&self, rack: &mut Rack, value: Control) {
@@ -49,10 +89,14 @@ impl WaveGuide {
 }
 
 impl Signal for WaveGuide {
-    tag!();
+    tag!("hz_inv", "cutoff", "decay", "transient");
 
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        rack.outputs[(self.tag, 0)] = rack.outputs[(self.mixer.tag(), 0)];
+        let mut out = rack.outputs[(self.mixer.tag(), 0)];
+        if let Some(source) = self.transient_source {
+            out += self.transient(rack) * rack.outputs[(source, 0)];
+        }
+        rack.outputs[(self.tag, 0)] = out;
     }
 }
 
@@ -62,6 +106,8 @@ pub struct WaveGuideBuilder {
     hz_inv: Control,
     cutoff: Control,
     decay: Control,
+    transient: Control,
+    transient_decay: f32,
 }
 
 impl WaveGuideBuilder {
@@ -71,11 +117,24 @@ impl WaveGuideBuilder {
             hz_inv: (1.0 / 440.0).into(),
             cutoff: 2000.0.into(),
             decay: 0.95.into(),
+            transient: 0.0.into(),
+            transient_decay: 0.02,
         }
     }
     build!(hz_inv);
     build!(cutoff);
     build!(decay);
+    /// Level of the noise transient. Leave it at 0 to build the string
+    /// without one.
+    pub fn transient<T: Into<Control>>(&mut self, value: T) -> &mut Self {
+        self.transient = value.into();
+        self
+    }
+    /// Decay time of the noise transient in seconds.
+    pub fn transient_decay(&mut self, value: f32) -> &mut Self {
+        self.transient_decay = value;
+        self
+    }
     pub fn rack(&self, rack: &mut Rack) -> Arc<WaveGuide> {
         let adsr = AdsrBuilder::exp_20()
             .attack(0.001)
@@ -90,11 +149,30 @@ impl WaveGuideBuilder {
         let lpf_vca = VcaBuilder::new(lpf.tag()).level(self.decay).rack(rack);
         rack.controls[(mixer.tag(), 0)] = Control::I(exciter.tag().into());
         rack.controls[(mixer.tag(), 1)] = Control::I(lpf_vca.tag().into());
+        let transient = (!matches!(self.transient, Control::F(x) if x == 0.0)).then(|| {
+            let noise = WhiteNoiseBuilder::new()
+                .dist(NoiseDistribution::Uni)
+                .rack(rack);
+            let transient_adsr = AdsrBuilder::exp_20()
+                .attack(0.001)
+                .decay(self.transient_decay)
+                .sustain(0.0)
+                .release(0.001)
+                .velocity_sensitivity(1.0)
+                .rack(rack);
+            let source = ProductBuilder::new(vec![noise.tag(), transient_adsr.tag()]).rack(rack);
+            (transient_adsr, source.tag())
+        });
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.hz_inv;
         rack.controls[(n, 1)] = self.cutoff;
         rack.controls[(n, 2)] = self.decay;
-        let wg = Arc::new(WaveGuide::new(n, self.burst, adsr, mixer));
+        rack.controls[(n, 3)] = self.transient;
+        let mut wg = WaveGuide::new(n, self.burst, adsr, mixer);
+        if let Some((transient_adsr, source)) = transient {
+            wg = wg.with_transient(transient_adsr, source);
+        }
+        let wg = Arc::new(wg);
         rack.push(wg.clone());
         wg
     }
@@ -25,6 +25,12 @@
 //! [`Signal`]: signal/trait.Signal.html
 //! [`Rack`]: signal/struct.Rack.html
 
+/// Signal analysis.
+pub mod analysis;
+/// Level meters and other dynamics processors.
+pub mod dynamics;
+/// Modulation and time based effects.
+pub mod effects;
 /// Envelope generators.
 pub mod envelopes;
 /// A collection of some basic audio filters.
@@ -38,9 +44,14 @@ pub mod oscillators;
 /// Core Oscen types and traits.
 pub mod rack;
 /// An implementation of *freeverb*.
-// pub mod reverb;
+pub mod reverb;
+/// Sample playback.
+pub mod sampler;
 /// Wave shaping.
 pub mod shaping;
+// Helpers for the tests of this crate, not part of its documented API.
+#[doc(hidden)]
+pub mod testing;
 /// Utilites.
 pub mod utils;
 // Instruments.
@@ -8,35 +8,259 @@ use std::error::Error;
 use std::io::{stdin, stdout, Write};
 use std::sync::Arc;
 
+/// Maps midi notes to frequencies in hz.
+pub trait Tuning {
+    fn frequency(&self, note: u8) -> f32;
+
+    /// The frequency of a fractional step, interpolated exponentially between
+    /// the two neighbouring notes.
+    fn frequency_step(&self, step: f32) -> f32 {
+        let low = step.floor().clamp(0.0, 127.0);
+        let high = (low + 1.0).min(127.0);
+        let low_hz = self.frequency(low as u8);
+        let high_hz = self.frequency(high as u8);
+        low_hz * (high_hz / low_hz).powf((step - low).clamp(0.0, 1.0))
+    }
+}
+
+/// Equal temperament with `notes_per_octave` equal divisions of the octave and
+/// `reference_note` tuned to `reference_hz`.
 #[derive(Debug, Copy, Clone)]
+pub struct EqualTemperament {
+    pub notes_per_octave: f32,
+    pub reference_note: u8,
+    pub reference_hz: f32,
+}
+
+impl Default for EqualTemperament {
+    /// Standard 12 tone equal temperament with A4 (note 69) at 440 hz.
+    fn default() -> Self {
+        Self {
+            notes_per_octave: 12.0,
+            reference_note: 69,
+            reference_hz: 440.0,
+        }
+    }
+}
+
+impl EqualTemperament {
+    pub fn new(notes_per_octave: f32) -> Self {
+        Self {
+            notes_per_octave,
+            ..Default::default()
+        }
+    }
+}
+
+impl Tuning for EqualTemperament {
+    fn frequency(&self, note: u8) -> f32 {
+        self.frequency_step(note as f32)
+    }
+
+    fn frequency_step(&self, step: f32) -> f32 {
+        if self.notes_per_octave == 12.0 && self.reference_note == 69 && self.reference_hz == 440.0
+        {
+            return hz_from_step(step);
+        }
+        let steps = step - self.reference_note as f32;
+        self.reference_hz * 2f32.powf(steps / self.notes_per_octave)
+    }
+}
+
+/// A scale given as the pitch of each degree in cents above the root, the last
+/// degree being the period (usually the octave, 1200 cents). `base_note` is
+/// tuned to `base_hz` and the scale repeats every `degrees.len()` notes.
+#[derive(Debug, Clone)]
+pub struct ScalaTuning {
+    degrees: Vec<f32>,
+    base_note: u8,
+    base_hz: f32,
+}
+
+impl ScalaTuning {
+    pub fn new(degrees: Vec<f32>, base_note: u8, base_hz: f32) -> Self {
+        assert!(!degrees.is_empty(), "a scale needs at least one degree");
+        Self {
+            degrees,
+            base_note,
+            base_hz,
+        }
+    }
+
+    /// Parse the contents of a Scala `.scl` file. Pitches containing a `.` are
+    /// in cents, all others are ratios such as `3/2` or `2`.
+    pub fn from_scl(scl: &str, base_note: u8, base_hz: f32) -> Result<Self, Box<dyn Error>> {
+        let mut lines = scl
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.starts_with('!'));
+        let _description = lines.next().ok_or("missing description")?;
+        let count: usize = lines
+            .next()
+            .ok_or("missing number of notes")?
+            .split_whitespace()
+            .next()
+            .ok_or("missing number of notes")?
+            .parse()?;
+        let mut degrees = Vec::with_capacity(count);
+        for line in lines.take(count) {
+            let pitch = line.split_whitespace().next().ok_or("empty pitch")?;
+            let cents = if pitch.contains('.') {
+                pitch.parse::<f32>()?
+            } else {
+                let ratio = match pitch.split_once('/') {
+                    Some((n, d)) => n.parse::<f32>()? / d.parse::<f32>()?,
+                    None => pitch.parse::<f32>()?,
+                };
+                1200.0 * ratio.log2()
+            };
+            degrees.push(cents);
+        }
+        if degrees.len() != count || count == 0 {
+            return Err("wrong number of pitches".into());
+        }
+        Ok(Self::new(degrees, base_note, base_hz))
+    }
+}
+
+impl Tuning for ScalaTuning {
+    fn frequency(&self, note: u8) -> f32 {
+        let n = self.degrees.len() as i32;
+        let steps = note as i32 - self.base_note as i32;
+        let period = self.degrees[self.degrees.len() - 1];
+        let degree = steps.rem_euclid(n) as usize;
+        let offset = if degree == 0 {
+            0.0
+        } else {
+            self.degrees[degree - 1]
+        };
+        let cents = steps.div_euclid(n) as f32 * period + offset;
+        self.base_hz * 2f32.powf(cents / 1200.0)
+    }
+}
+
+/// When a `MidiPitch` with a non zero `glide` time should glide to a new note.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum GlideMode {
+    /// Glide on every note change, however the step is set.
+    Always,
+    /// Only glide when the new note is played while another is still held,
+    /// otherwise jump straight to the new pitch. Notes are only counted as
+    /// held between `MidiPitch::note_on` and `note_off`, so a step set any
+    /// other way, e.g. with `set_step` or by patching it, never glides.
+    #[default]
+    Legato,
+}
+
+/// Converts a midi step to a frequency in hz. When `glide` is greater than zero
+/// the output moves linearly from the previous frequency to the new one over
+/// `glide` seconds, as set by the `GlideMode`.
+#[derive(Clone)]
 pub struct MidiPitch {
     tag: Tag,
+    glide_mode: GlideMode,
+    tuning: Arc<dyn Tuning + Send + Sync>,
+}
+
+impl std::fmt::Debug for MidiPitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MidiPitch")
+            .field("tag", &self.tag)
+            .field("glide_mode", &self.glide_mode)
+            .finish_non_exhaustive()
+    }
 }
 
 impl MidiPitch {
+    /// Equal temperament and `GlideMode::Legato`.
     pub fn new(tag: Tag) -> Self {
-        Self { tag }
+        Self {
+            tag,
+            glide_mode: GlideMode::default(),
+            tuning: Arc::new(EqualTemperament::default()),
+        }
+    }
+
+    pub fn with_glide_mode(mut self, mode: GlideMode) -> Self {
+        self.glide_mode = mode;
+        self
+    }
+
+    pub fn with_tuning(mut self, tuning: Arc<dyn Tuning + Send + Sync>) -> Self {
+        self.tuning = tuning;
+        self
     }
 
     props!(step, set_step, 0);
     props!(offset, set_offset, 1);
     props!(factor, set_factor, 2);
+    props!(glide, set_glide, 3);
+
+    pub fn glide_mode(&self) -> GlideMode {
+        self.glide_mode
+    }
+
+    /// Set the step and count the note as held until the matching `note_off`.
+    pub fn note_on(&self, rack: &mut Rack, step: f32) {
+        let held = rack.state[(self.tag, 3)];
+        rack.state[(self.tag, 4)] = if held > 0.0 { 1.0 } else { 0.0 };
+        rack.state[(self.tag, 3)] = held + 1.0;
+        self.set_step(rack, step.into());
+    }
+
+    pub fn note_off(&self, rack: &mut Rack) {
+        let held = (rack.state[(self.tag, 3)] - 1.0).max(0.0);
+        rack.state[(self.tag, 3)] = held;
+        if held == 0.0 {
+            rack.state[(self.tag, 4)] = 0.0;
+        }
+    }
 }
 
 impl Signal for MidiPitch {
-    tag!();
+    tag!("step", "offset", "factor", "glide");
 
-    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        rack.outputs[(self.tag, 0)] =
-            hz_from_step(self.factor(rack) * self.step(rack) + self.offset(rack));
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let target = self
+            .tuning
+            .frequency_step(self.factor(rack) * self.step(rack) + self.offset(rack));
+        let glide = self.glide(rack);
+        if target != rack.state[(tag, 5)] {
+            let legato = rack.state[(tag, 4)] > 0.0;
+            let glide_on = match self.glide_mode {
+                GlideMode::Always => true,
+                GlideMode::Legato => legato,
+            };
+            if glide > 0.0 && glide_on && rack.state[(tag, 5)] > 0.0 {
+                rack.state[(tag, 1)] = rack.state[(tag, 0)];
+                rack.state[(tag, 2)] = 0.0;
+            } else {
+                rack.state[(tag, 2)] = glide;
+            }
+            rack.state[(tag, 5)] = target;
+        }
+        let elapsed = rack.state[(tag, 2)];
+        let out = if elapsed < glide {
+            let start = rack.state[(tag, 1)];
+            start + (target - start) * elapsed / glide
+        } else {
+            target
+        };
+        rack.state[(tag, 2)] = elapsed + 1.0 / sample_rate;
+        rack.state[(tag, 0)] = out;
+        rack.outputs[(tag, 0)] = out;
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Clone)]
 pub struct MidiPitchBuilder {
     step: Control,
     offset: Control,
     factor: Control,
+    glide: Control,
+    glide_mode: GlideMode,
+    tuning: Arc<dyn Tuning + Send + Sync>,
 }
 
 impl Default for MidiPitchBuilder {
@@ -45,6 +269,9 @@ impl Default for MidiPitchBuilder {
             step: 0.0.into(),
             offset: 0.0.into(),
             factor: 1.0.into(),
+            glide: 0.0.into(),
+            glide_mode: GlideMode::default(),
+            tuning: Arc::new(EqualTemperament::default()),
         }
     }
 }
@@ -57,18 +284,161 @@ impl MidiPitchBuilder {
     build!(step);
     build!(offset);
     build!(factor);
+    build!(glide);
+
+    pub fn glide_mode(&mut self, value: GlideMode) -> &mut Self {
+        self.glide_mode = value;
+        self
+    }
+
+    pub fn tuning<T: Tuning + Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.tuning = Arc::new(value);
+        self
+    }
 
     pub fn rack(&self, rack: &mut Rack) -> Arc<MidiPitch> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.step;
         rack.controls[(n, 1)] = self.offset;
         rack.controls[(n, 2)] = self.factor;
-        let mp = Arc::new(MidiPitch::new(n.into()));
+        rack.controls[(n, 3)] = self.glide;
+        let mp = Arc::new(
+            MidiPitch::new(n.into())
+                .with_glide_mode(self.glide_mode)
+                .with_tuning(self.tuning.clone()),
+        );
         rack.push(mp.clone());
         mp
     }
 }
 
+/// A set of pitch classes, given as semitones above the root of the scale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scale {
+    degrees: Vec<u8>,
+}
+
+impl Scale {
+    /// A custom scale. Degrees are reduced to a single octave, sorted and
+    /// deduplicated. An empty scale is treated as chromatic.
+    pub fn new(degrees: &[u8]) -> Self {
+        let mut degrees: Vec<u8> = degrees.iter().map(|d| d % 12).collect();
+        degrees.sort_unstable();
+        degrees.dedup();
+        if degrees.is_empty() {
+            return Self::chromatic();
+        }
+        Self { degrees }
+    }
+
+    pub fn chromatic() -> Self {
+        Self {
+            degrees: (0..12).collect(),
+        }
+    }
+
+    pub fn major() -> Self {
+        Self::new(&[0, 2, 4, 5, 7, 9, 11])
+    }
+
+    pub fn minor() -> Self {
+        Self::new(&[0, 2, 3, 5, 7, 8, 10])
+    }
+
+    pub fn major_pentatonic() -> Self {
+        Self::new(&[0, 2, 4, 7, 9])
+    }
+
+    pub fn minor_pentatonic() -> Self {
+        Self::new(&[0, 3, 5, 7, 10])
+    }
+
+    pub fn degrees(&self) -> &[u8] {
+        &self.degrees
+    }
+
+    /// The note of the scale nearest to `step` (in semitones), when the scale
+    /// starts at `root`. Ties go to the lower note.
+    pub fn quantize(&self, step: f32, root: f32) -> f32 {
+        let rel = step - root;
+        let octave = (rel / 12.0).floor();
+        let within = rel - 12.0 * octave;
+        let mut best = self.degrees[0] as f32;
+        for d in self
+            .degrees
+            .iter()
+            .map(|d| *d as f32)
+            .chain([12.0 + self.degrees[0] as f32])
+        {
+            if (within - d).abs() < (within - best).abs() {
+                best = d;
+            }
+        }
+        root + 12.0 * octave + best
+    }
+}
+
+/// Snaps the midi step `pitch` to the nearest note of a `Scale` starting at
+/// `root` (a midi step, only its pitch class matters).
+#[derive(Debug, Clone)]
+pub struct Quantizer {
+    tag: Tag,
+    scale: Scale,
+}
+
+impl Quantizer {
+    pub fn new<T: Into<Tag>>(tag: T, scale: Scale) -> Self {
+        Self {
+            tag: tag.into(),
+            scale,
+        }
+    }
+
+    props!(pitch, set_pitch, 0);
+    props!(root, set_root, 1);
+
+    pub fn scale(&self) -> &Scale {
+        &self.scale
+    }
+}
+
+impl Signal for Quantizer {
+    tag!("pitch", "root");
+
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        rack.outputs[(self.tag, 0)] = self.scale.quantize(self.pitch(rack), self.root(rack));
+    }
+}
+
+#[derive(Clone)]
+pub struct QuantizerBuilder {
+    scale: Scale,
+    pitch: Control,
+    root: Control,
+}
+
+impl QuantizerBuilder {
+    pub fn new(scale: Scale) -> Self {
+        Self {
+            scale,
+            pitch: 60.0.into(),
+            root: 0.0.into(),
+        }
+    }
+
+    build!(pitch);
+    build!(root);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Quantizer> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.pitch;
+        rack.controls[(n, 1)] = self.root;
+        let q = Arc::new(Quantizer::new(n, self.scale.clone()));
+        rack.push(q.clone());
+        q
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct MidiControl {
     tag: Tag,
@@ -135,7 +505,7 @@ impl MidiControl {
 }
 
 impl Signal for MidiControl {
-    tag!();
+    tag!("value");
 
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let value = self.value(rack);
@@ -195,7 +565,686 @@ impl MidiControlBuilder {
     }
 }
 
+/// Controller number of the sustain (damper) pedal.
+pub const SUSTAIN_PEDAL: u8 = 64;
+
+/// A decoded midi channel message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// Polyphonic aftertouch, the pressure on a single held key.
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    /// Channel aftertouch, one pressure for the whole keyboard.
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    /// Timing clock, sent 24 times per quarter note.
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+impl MidiMessage {
+    /// Decode the raw bytes of a message, e.g. as sent by `listen_midi`. A note
+    /// on with zero velocity is decoded as a note off.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let status = *bytes.first()?;
+        match status {
+            0xF8 => return Some(MidiMessage::Clock),
+            0xFA => return Some(MidiMessage::Start),
+            0xFB => return Some(MidiMessage::Continue),
+            0xFC => return Some(MidiMessage::Stop),
+            _ => {}
+        }
+        let channel = status & 0x0F;
+        let data1 = *bytes.get(1)?;
+        if status & 0xF0 == 0xD0 {
+            return Some(MidiMessage::ChannelPressure {
+                channel,
+                pressure: data1,
+            });
+        }
+        let data2 = *bytes.get(2)?;
+        match status & 0xF0 {
+            0x80 => Some(MidiMessage::NoteOff {
+                channel,
+                note: data1,
+                velocity: data2,
+            }),
+            0x90 if data2 == 0 => Some(MidiMessage::NoteOff {
+                channel,
+                note: data1,
+                velocity: 0,
+            }),
+            0x90 => Some(MidiMessage::NoteOn {
+                channel,
+                note: data1,
+                velocity: data2,
+            }),
+            0xA0 => Some(MidiMessage::PolyPressure {
+                channel,
+                note: data1,
+                pressure: data2,
+            }),
+            0xB0 => Some(MidiMessage::ControlChange {
+                channel,
+                controller: data1,
+                value: data2,
+            }),
+            _ => None,
+        }
+    }
+
+    /// `Some(true)` if this message presses the sustain pedal, `Some(false)` if
+    /// it lifts it and `None` for any other message.
+    pub fn sustain(&self) -> Option<bool> {
+        match self {
+            MidiMessage::ControlChange {
+                controller, value, ..
+            } if *controller == SUSTAIN_PEDAL => Some(*value >= 64),
+            _ => None,
+        }
+    }
+}
+
+/// A midi message to be handled `frame_offset` samples into the current
+/// block.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimedMidiMessage {
+    pub frame_offset: usize,
+    pub message: MidiMessage,
+}
+
+impl TimedMidiMessage {
+    pub fn new(frame_offset: usize, message: MidiMessage) -> Self {
+        Self {
+            frame_offset,
+            message,
+        }
+    }
+}
+
+/// Interleave the messages of `N` sources, e.g. a sequencer and a live midi
+/// input that drive the same `VoiceAllocator`, into one stream ordered by
+/// `frame_offset`. The sources don't need to be in order themselves. Messages
+/// with the same offset keep their order within a source and earlier sources
+/// come first. The result is a new `Vec`, so merge outside the audio thread.
+pub fn merge_midi<const N: usize>(sources: [&[TimedMidiMessage]; N]) -> Vec<TimedMidiMessage> {
+    let mut merged: Vec<TimedMidiMessage> = sources.concat();
+    merged.sort_by_key(|m| m.frame_offset);
+    merged
+}
+
+/// Exposes the latest value of a set of midi controllers, each normalized to
+/// 0..1, as the outputs of a single module. Use `output` to patch a controller
+/// into another module.
+#[derive(Debug, Clone)]
+pub struct MidiCcParser {
+    tag: Tag,
+    controllers: Vec<u8>,
+}
+
+impl MidiCcParser {
+    pub fn new(tag: Tag, controllers: Vec<u8>) -> Self {
+        assert!(
+            controllers.len() <= MAX_OUTPUTS,
+            "MidiCcParser supports at most {MAX_OUTPUTS} controllers"
+        );
+        Self { tag, controllers }
+    }
+
+    pub fn controllers(&self) -> &[u8] {
+        &self.controllers
+    }
+
+    /// A control connected to the output for `controller`.
+    pub fn output(&self, controller: u8) -> Option<Control> {
+        let i = self.controllers.iter().position(|c| *c == controller)?;
+        Some(Control::V(self.tag, i))
+    }
+
+    pub fn value(&self, rack: &Rack, controller: u8) -> Option<f32> {
+        let i = self.controllers.iter().position(|c| *c == controller)?;
+        Some(rack.state[(self.tag, i)])
+    }
+
+    /// Decode raw midi bytes and store the value if it is a control change
+    /// for one of our controllers.
+    pub fn message(&self, rack: &mut Rack, bytes: &[u8]) {
+        if let Some(MidiMessage::ControlChange {
+            controller, value, ..
+        }) = MidiMessage::from_bytes(bytes)
+        {
+            if let Some(i) = self.controllers.iter().position(|c| *c == controller) {
+                rack.state[(self.tag, i)] = value as f32 / 127.0;
+            }
+        }
+    }
+}
+
+impl Signal for MidiCcParser {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        for i in 0..self.controllers.len() {
+            rack.outputs[(self.tag, i)] = rack.state[(self.tag, i)];
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MidiCcParserBuilder {
+    controllers: Vec<u8>,
+}
+
+impl MidiCcParserBuilder {
+    pub fn new(controllers: &[u8]) -> Self {
+        Self {
+            controllers: controllers.to_vec(),
+        }
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<MidiCcParser> {
+        let n = rack.num_modules();
+        let mcp = Arc::new(MidiCcParser::new(n.into(), self.controllers.clone()));
+        rack.push(mcp.clone());
+        mcp
+    }
+}
+
+/// Exposes the latest channel aftertouch, normalized to 0..1, as its output.
+#[derive(Debug, Copy, Clone)]
+pub struct MidiPressure {
+    tag: Tag,
+}
+
+impl MidiPressure {
+    pub fn new<T: Into<Tag>>(tag: T) -> Self {
+        Self { tag: tag.into() }
+    }
+
+    pub fn value(&self, rack: &Rack) -> f32 {
+        rack.state[(self.tag, 0)]
+    }
+
+    /// Decode raw midi bytes and store the pressure if it is channel
+    /// aftertouch.
+    pub fn message(&self, rack: &mut Rack, bytes: &[u8]) {
+        if let Some(MidiMessage::ChannelPressure { pressure, .. }) = MidiMessage::from_bytes(bytes)
+        {
+            rack.state[(self.tag, 0)] = pressure as f32 / 127.0;
+        }
+    }
+}
+
+impl Signal for MidiPressure {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        rack.outputs[(self.tag, 0)] = rack.state[(self.tag, 0)];
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MidiPressureBuilder;
+
+impl MidiPressureBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<MidiPressure> {
+        let n = rack.num_modules();
+        let pressure = Arc::new(MidiPressure::new(n));
+        rack.push(pressure.clone());
+        pressure
+    }
+}
+
+/// Midi clock ticks per quarter note.
+pub const CLOCK_PPQN: f32 = 24.0;
+
+/// A change of transport state, returned by `MidiClock::message`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transport {
+    Start,
+    Continue,
+    Stop,
+}
+
+/// Follows an external midi clock. Output 0 is the tempo in beats per minute,
+/// estimated from the spacing of the clock ticks counted in samples, and 0
+/// until two ticks have arrived. Output 1 is 1 while the transport is running
+/// and 0 when stopped.
+#[derive(Debug, Copy, Clone)]
+pub struct MidiClock {
+    tag: Tag,
+}
+
+impl MidiClock {
+    pub fn new<T: Into<Tag>>(tag: T) -> Self {
+        Self { tag: tag.into() }
+    }
+
+    pub fn bpm(&self, rack: &Rack) -> f32 {
+        rack.outputs[(self.tag, 0)]
+    }
+
+    pub fn running(&self, rack: &Rack) -> bool {
+        rack.state[(self.tag, 3)] > 0.0
+    }
+
+    /// Clock ticks since the last start, e.g. to find the position in a bar.
+    pub fn ticks(&self, rack: &Rack) -> usize {
+        rack.state[(self.tag, 4)] as usize
+    }
+
+    /// Decode raw midi bytes, timing a clock tick or returning the new
+    /// transport state.
+    pub fn message(&self, rack: &mut Rack, bytes: &[u8]) -> Option<Transport> {
+        // state: 0 = samples since the last tick, 1 = average samples per
+        // tick, 2 = 1 once a tick has arrived, 3 = running, 4 = ticks.
+        let tag = self.tag;
+        let transport = match MidiMessage::from_bytes(bytes)? {
+            MidiMessage::Clock => {
+                let interval = rack.state[(tag, 0)];
+                let average = rack.state[(tag, 1)];
+                if rack.state[(tag, 2)] > 0.0 {
+                    // Average over about a quarter note to smooth out jitter.
+                    rack.state[(tag, 1)] = if average > 0.0 {
+                        average + (interval - average) / CLOCK_PPQN
+                    } else {
+                        interval
+                    };
+                }
+                rack.state[(tag, 0)] = 0.0;
+                rack.state[(tag, 2)] = 1.0;
+                if rack.state[(tag, 3)] > 0.0 {
+                    rack.state[(tag, 4)] += 1.0;
+                }
+                return None;
+            }
+            MidiMessage::Start => {
+                rack.state[(tag, 4)] = 0.0;
+                Transport::Start
+            }
+            MidiMessage::Continue => Transport::Continue,
+            MidiMessage::Stop => Transport::Stop,
+            _ => return None,
+        };
+        rack.state[(tag, 3)] = if transport == Transport::Stop {
+            0.0
+        } else {
+            1.0
+        };
+        Some(transport)
+    }
+}
+
+impl Signal for MidiClock {
+    tag!();
+    fn output_names(&self) -> &'static [&'static str] {
+        &["bpm", "running"]
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        rack.state[(tag, 0)] += 1.0;
+        let average = rack.state[(tag, 1)];
+        rack.outputs[(tag, 0)] = if average > 0.0 {
+            60.0 * sample_rate / (CLOCK_PPQN * average)
+        } else {
+            0.0
+        };
+        rack.outputs[(tag, 1)] = rack.state[(tag, 3)];
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MidiClockBuilder;
+
+impl MidiClockBuilder {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<MidiClock> {
+        let n = rack.num_modules();
+        let clock = Arc::new(MidiClock::new(n));
+        rack.push(clock.clone());
+        clock
+    }
+}
+
+/// What a `VoiceAllocator` does with a new note when every voice is busy.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VoiceStealMode {
+    /// Steal the voice whose note started longest ago.
+    Oldest,
+    /// Steal the voice with the lowest level, see `VoiceAllocator::set_level`.
+    Quietest,
+    /// Never steal, the new note waits until a voice is released.
+    None,
+}
+
+/// Which note wins when more notes are held than there are voices.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NotePriority {
+    /// The most recently played note wins.
+    Last,
+    /// Notes that are already sounding win.
+    First,
+    /// The highest note wins.
+    Highest,
+    /// The lowest note wins.
+    Lowest,
+}
+
+/// Instruction returned by a `VoiceAllocator`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VoiceCommand {
+    /// Start (or retrigger) `voice` playing `note`.
+    On { voice: usize, note: u8 },
+    /// Release `voice`.
+    Off { voice: usize },
+    /// Aftertouch on the note `voice` is playing.
+    Pressure { voice: usize, pressure: u8 },
+    /// `voice` is about to be taken for another note while still sounding,
+    /// e.g. to fade it out quickly with `Adsr::fast_release`. Only sent by
+    /// `VoiceAllocator::message`, right before the `On`.
+    Steal { voice: usize },
+}
+
+/// The commands returned by `VoiceAllocator::message` and `set_sustain`, at
+/// most one per voice and one more, kept without allocating so that midi can
+/// be handled on the audio thread. Iterate over it to get the commands in
+/// order.
+#[derive(Debug, Copy, Clone)]
+pub struct VoiceCommands<const N: usize> {
+    commands: [Option<VoiceCommand>; N],
+    extra: Option<VoiceCommand>,
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> VoiceCommands<N> {
+    fn new() -> Self {
+        Self {
+            commands: [None; N],
+            extra: None,
+            len: 0,
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, command: VoiceCommand) {
+        match self.commands.get_mut(self.len) {
+            Some(slot) => *slot = Some(command),
+            None => self.extra = Some(command),
+        }
+        self.len += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next >= self.len
+    }
+}
+
+impl<const N: usize> Iterator for VoiceCommands<N> {
+    type Item = VoiceCommand;
+
+    fn next(&mut self) -> Option<VoiceCommand> {
+        if self.is_empty() {
+            return None;
+        }
+        let i = self.next;
+        self.next += 1;
+        match self.commands.get(i) {
+            Some(command) => *command,
+            None => self.extra,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.next;
+        (n, Some(n))
+    }
+}
+
+/// Keeps track of which of `N` voices is playing which note. The allocator
+/// does not own the voices, it returns a `VoiceCommand` telling the caller
+/// which voice to trigger or release.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator<const N: usize> {
+    steal_mode: VoiceStealMode,
+    priority: NotePriority,
+    notes: [Option<u8>; N],
+    ages: [u64; N],
+    levels: [f32; N],
+    held: Vec<u8>,
+    counter: u64,
+    sustain: bool,
+    sustained: [bool; N],
+}
+
+impl<const N: usize> Default for VoiceAllocator<N> {
+    fn default() -> Self {
+        Self::new(VoiceStealMode::Oldest, NotePriority::Last)
+    }
+}
+
+impl<const N: usize> VoiceAllocator<N> {
+    pub fn new(steal_mode: VoiceStealMode, priority: NotePriority) -> Self {
+        Self {
+            steal_mode,
+            priority,
+            notes: [None; N],
+            ages: [0; N],
+            levels: [0.0; N],
+            held: Vec::with_capacity(128),
+            counter: 0,
+            sustain: false,
+            sustained: [false; N],
+        }
+    }
+
+    pub fn steal_mode(&self) -> VoiceStealMode {
+        self.steal_mode
+    }
+
+    pub fn priority(&self) -> NotePriority {
+        self.priority
+    }
+
+    /// The note `voice` is currently playing.
+    pub fn note(&self, voice: usize) -> Option<u8> {
+        self.notes[voice]
+    }
+
+    /// The voice currently playing `note`.
+    pub fn voice(&self, note: u8) -> Option<usize> {
+        self.notes.iter().position(|n| *n == Some(note))
+    }
+
+    /// Amplitude hint used by `VoiceStealMode::Quietest`, e.g. the current
+    /// output of the voice's envelope.
+    pub fn set_level(&mut self, voice: usize, level: f32) {
+        self.levels[voice] = level;
+    }
+
+    pub fn sustain(&self) -> bool {
+        self.sustain
+    }
+
+    /// Press or lift the sustain pedal. While the pedal is down note offs are
+    /// held back, lifting it releases every voice whose note was let go in the
+    /// meantime.
+    pub fn set_sustain(&mut self, value: bool) -> VoiceCommands<N> {
+        self.sustain = value;
+        let mut commands = VoiceCommands::new();
+        if !value {
+            for voice in 0..N {
+                if self.sustained[voice] {
+                    commands.push(self.release(voice));
+                }
+            }
+        }
+        commands
+    }
+
+    /// Route a decoded midi message to `note_on`, `note_off`, `pressure` or
+    /// `set_sustain`. A note on that takes a sounding voice is preceded by a
+    /// `VoiceCommand::Steal` of it.
+    pub fn message(&mut self, message: MidiMessage) -> VoiceCommands<N> {
+        if let Some(sustain) = message.sustain() {
+            return self.set_sustain(sustain);
+        }
+        let mut commands = VoiceCommands::new();
+        let command = match message {
+            MidiMessage::NoteOn { note, .. } => {
+                let notes = self.notes;
+                let command = self.note_on(note);
+                if let Some(VoiceCommand::On { voice, .. }) = command {
+                    if notes[voice].is_some_and(|n| n != note) {
+                        commands.push(VoiceCommand::Steal { voice });
+                    }
+                }
+                command
+            }
+            MidiMessage::NoteOff { note, .. } => self.note_off(note),
+            MidiMessage::PolyPressure { note, pressure, .. } => self.pressure(note, pressure),
+            _ => None,
+        };
+        if let Some(command) = command {
+            commands.push(command);
+        }
+        commands
+    }
+
+    fn assign(&mut self, voice: usize, note: u8) -> VoiceCommand {
+        self.counter += 1;
+        self.sustained[voice] = false;
+        self.notes[voice] = Some(note);
+        self.ages[voice] = self.counter;
+        VoiceCommand::On { voice, note }
+    }
+
+    fn victim(&self, note: u8) -> Option<usize> {
+        if self.steal_mode == VoiceStealMode::None {
+            return None;
+        }
+        let playing = |v: &usize| self.notes[*v];
+        match self.priority {
+            NotePriority::First => None,
+            NotePriority::Highest => (0..N)
+                .min_by_key(playing)
+                .filter(|v| self.notes[*v] < Some(note)),
+            NotePriority::Lowest => (0..N)
+                .max_by_key(playing)
+                .filter(|v| self.notes[*v] > Some(note)),
+            NotePriority::Last => match self.steal_mode {
+                VoiceStealMode::Quietest => (0..N).min_by(|a, b| {
+                    self.levels[*a]
+                        .total_cmp(&self.levels[*b])
+                        .then(self.ages[*a].cmp(&self.ages[*b]))
+                }),
+                _ => (0..N).min_by_key(|v| self.ages[*v]),
+            },
+        }
+    }
+
+    /// The held note that is not sounding and has the highest priority.
+    fn waiting(&self) -> Option<u8> {
+        let mut waiting = self
+            .held
+            .iter()
+            .copied()
+            .filter(|n| self.voice(*n).is_none());
+        match self.priority {
+            NotePriority::Last => waiting.next_back(),
+            NotePriority::First => waiting.next(),
+            NotePriority::Highest => waiting.max(),
+            NotePriority::Lowest => waiting.min(),
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8) -> Option<VoiceCommand> {
+        self.held.retain(|n| *n != note);
+        self.held.push(note);
+        if let Some(voice) = self.voice(note) {
+            return Some(self.assign(voice, note));
+        }
+        let free = (0..N)
+            .filter(|v| self.notes[*v].is_none())
+            .min_by_key(|v| self.ages[*v]);
+        free.or_else(|| self.victim(note))
+            .map(|voice| self.assign(voice, note))
+    }
+
+    pub fn note_off(&mut self, note: u8) -> Option<VoiceCommand> {
+        self.held.retain(|n| *n != note);
+        let voice = self.voice(note)?;
+        if self.sustain {
+            self.sustained[voice] = true;
+            return None;
+        }
+        Some(self.release(voice))
+    }
+
+    /// Route aftertouch on `note` to the voice playing it, if any.
+    pub fn pressure(&self, note: u8, pressure: u8) -> Option<VoiceCommand> {
+        let voice = self.voice(note)?;
+        Some(VoiceCommand::Pressure { voice, pressure })
+    }
+
+    fn release(&mut self, voice: usize) -> VoiceCommand {
+        self.sustained[voice] = false;
+        match self.waiting() {
+            Some(waiting) => self.assign(voice, waiting),
+            None => {
+                self.notes[voice] = None;
+                VoiceCommand::Off { voice }
+            }
+        }
+    }
+}
+
+/// Send the raw bytes of every message from a midi input port, chosen on the
+/// console if there is more than one, until enter is pressed.
 pub fn listen_midi(midi_sender: Sender<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    listen(midi_sender, |bytes| Some(bytes.to_vec()))
+}
+
+/// Like `listen_midi` but the messages are decoded on the midi thread and
+/// sent as `MidiMessage`s, which unlike the raw bytes need no allocation.
+/// Messages `MidiMessage::from_bytes` does not decode are dropped.
+pub fn listen_midi_messages(midi_sender: Sender<MidiMessage>) -> Result<(), Box<dyn Error>> {
+    listen(midi_sender, MidiMessage::from_bytes)
+}
+
+fn listen<T: Send + 'static>(
+    midi_sender: Sender<T>,
+    decode: fn(&[u8]) -> Option<T>,
+) -> Result<(), Box<dyn Error>> {
     let mut input = String::new();
     let mut midi_in = MidiInput::new("midir reading input")?;
     midi_in.ignore(Ignore::None);
@@ -233,7 +1282,9 @@ pub fn listen_midi(midi_sender: Sender<Vec<u8>>) -> Result<(), Box<dyn Error>> {
         in_port,
         "midir-read-input",
         move |_, message, _| {
-            midi_sender.send(message.to_vec()).unwrap();
+            if let Some(message) = decode(message) {
+                midi_sender.send(message).unwrap();
+            }
         },
         (),
     )?;
@@ -1,49 +1,82 @@
 use crate::oscillators::{ConstBuilder, OscBuilder};
 use crate::rack::*;
+use crate::utils::zap_denormal;
 use crate::{build, props, tag};
+use parking_lot::Mutex;
 use std::sync::Arc;
+/// Sums its inputs into output 0. With `taps` on, e.g. when the inputs are
+/// the voices of a polyphonic patch, output `1 + i` also passes on input `i`
+/// so each voice can be metered or drawn on its own.
 #[derive(Debug, Clone)]
 pub struct Mixer {
     tag: Tag,
     num_waves: u8,
+    taps: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct MixerBuilder {
     waves: Vec<Tag>,
+    taps: bool,
 }
 
 impl MixerBuilder {
     pub fn new(waves: Vec<Tag>) -> Self {
-        Self { waves }
+        Self { waves, taps: false }
+    }
+    pub fn taps(&mut self, value: bool) -> &mut Self {
+        self.taps = value;
+        self
     }
     pub fn rack(&self, rack: &mut Rack) -> Arc<Mixer> {
+        assert!(
+            !self.taps || self.waves.len() < MAX_OUTPUTS,
+            "Mixer taps support at most {} inputs",
+            MAX_OUTPUTS - 1
+        );
         let n = rack.num_modules();
         let cs = rack.controls.controls_mut(n);
         for (i, w) in self.waves.iter().enumerate() {
             cs[i] = Control::I((*w).into());
         }
         let nw = self.waves.len() as u8;
-        let mix = Arc::new(Mixer::new(n.into(), nw));
+        let mix = Arc::new(Mixer::new(n.into(), nw, self.taps));
         rack.push(mix.clone());
         mix
     }
 }
 
 impl Mixer {
-    fn new(tag: Tag, num_waves: u8) -> Self {
-        Self { tag, num_waves }
+    fn new(tag: Tag, num_waves: u8, taps: bool) -> Self {
+        Self {
+            tag,
+            num_waves,
+            taps,
+        }
+    }
+
+    /// A control connected to the tap of input `i`, if taps are on.
+    pub fn tap(&self, i: usize) -> Option<Control> {
+        (self.taps && i < self.num_waves as usize).then_some(Control::V(self.tag, i + 1))
     }
 }
 
 impl Signal for Mixer {
     tag!();
+    fn is_pure(&self) -> bool {
+        true
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        let cs = &rack.controls.controls(self.tag())[0..self.num_waves as usize];
-        rack.outputs[(self.tag, 0)] = cs
-            .iter()
-            .map(|x| x.idx())
-            .fold(0.0, |acc, n| acc + rack.outputs[(n, 0)]);
+        let n = self.num_waves as usize;
+        let mut sum = 0.0;
+        for i in 0..n {
+            let x = rack.outputs[(rack.controls[(self.tag, i)].idx(), 0)];
+            if self.taps {
+                rack.outputs[(self.tag, i + 1)] = x;
+            }
+            sum += x;
+        }
+        rack.outputs[(self.tag, 0)] = sum;
     }
 }
 
@@ -97,7 +130,7 @@ impl Union {
 }
 
 impl Signal for Union {
-    tag!();
+    tag!("active");
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let idx = self.active(rack);
         let cs = &rack.controls.controls(self.tag())[1..=self.num_waves as usize];
@@ -142,6 +175,9 @@ impl Product {
 
 impl Signal for Product {
     tag!();
+    fn is_pure(&self) -> bool {
+        true
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let cs = &rack.controls.controls(self.tag())[0..self.num_waves as usize];
         rack.outputs[(self.tag, 0)] = cs
@@ -189,6 +225,191 @@ impl InverseBuilder {
     }
 }
 
+/// Maps its input `x` to `scale * x + offset`, e.g. to turn a bipolar LFO
+/// into a cutoff frequency. Use a `Mixer` to add and a `Product` to multiply
+/// the outputs of several modules.
+#[derive(Debug, Copy, Clone)]
+pub struct ScaleOffset {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl ScaleOffset {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(scale, set_scale, 0);
+    props!(offset, set_offset, 1);
+}
+
+impl Signal for ScaleOffset {
+    tag!("scale", "offset");
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        rack.outputs[(self.tag, 0)] =
+            self.scale(rack) * rack.outputs[(self.wave, 0)] + self.offset(rack);
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct ScaleOffsetBuilder {
+    wave: Tag,
+    scale: Control,
+    offset: Control,
+}
+
+impl ScaleOffsetBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            scale: 1.0.into(),
+            offset: 0.0.into(),
+        }
+    }
+    build!(scale);
+    build!(offset);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<ScaleOffset> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.scale;
+        rack.controls[(n, 1)] = self.offset;
+        let so = Arc::new(ScaleOffset::new(n, self.wave));
+        rack.push(so.clone());
+        so
+    }
+}
+
+/// Outputs 1 for a single sample each time its input rises through
+/// `threshold`, and 0 otherwise. Turns a gate that stays high while a note is
+/// held into a trigger that fires once when it starts.
+#[derive(Debug, Copy, Clone)]
+pub struct GateToTrigger {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl GateToTrigger {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(threshold, set_threshold, 0);
+}
+
+impl Signal for GateToTrigger {
+    tag!("threshold");
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let x = rack.outputs[(self.wave, 0)];
+        let threshold = self.threshold(rack);
+        let rising = rack.state[(self.tag, 0)] < threshold && x >= threshold;
+        rack.state[(self.tag, 0)] = x;
+        rack.outputs[(self.tag, 0)] = if rising { 1.0 } else { 0.0 };
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct GateToTriggerBuilder {
+    wave: Tag,
+    threshold: Control,
+}
+
+impl GateToTriggerBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            threshold: 0.5.into(),
+        }
+    }
+    build!(threshold);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<GateToTrigger> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.threshold;
+        let gt = Arc::new(GateToTrigger::new(n, self.wave));
+        rack.push(gt.clone());
+        gt
+    }
+}
+
+/// Holds its output at 1 for `length` seconds each time its input rises
+/// through `threshold`, e.g. to give a sequencer's one sample triggers a gate
+/// an envelope can sustain through. A trigger while the gate is open starts
+/// the length over.
+#[derive(Debug, Copy, Clone)]
+pub struct TriggerToGate {
+    tag: Tag,
+    wave: Tag,
+}
+
+impl TriggerToGate {
+    pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+        }
+    }
+    props!(length, set_length, 0);
+    props!(threshold, set_threshold, 1);
+}
+
+impl Signal for TriggerToGate {
+    tag!("length", "threshold");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        // state: 0 = previous input, 1 = samples left with the gate open.
+        let x = rack.outputs[(self.wave, 0)];
+        let threshold = self.threshold(rack);
+        if rack.state[(self.tag, 0)] < threshold && x >= threshold {
+            rack.state[(self.tag, 1)] = (self.length(rack) * sample_rate).round();
+        }
+        rack.state[(self.tag, 0)] = x;
+        let left = rack.state[(self.tag, 1)];
+        rack.outputs[(self.tag, 0)] = if left > 0.0 { 1.0 } else { 0.0 };
+        rack.state[(self.tag, 1)] = (left - 1.0).max(0.0);
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct TriggerToGateBuilder {
+    wave: Tag,
+    length: Control,
+    threshold: Control,
+}
+
+impl TriggerToGateBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self {
+            wave,
+            length: 0.1.into(),
+            threshold: 0.5.into(),
+        }
+    }
+    build!(length);
+    build!(threshold);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<TriggerToGate> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.length;
+        rack.controls[(n, 1)] = self.threshold;
+        let tg = Arc::new(TriggerToGate::new(n, self.wave));
+        rack.push(tg.clone());
+        tg
+    }
+}
+
+/// Gain at or below this many decibels is treated as silence.
+pub const MIN_DB: f32 = -100.0;
+
+/// Convert decibels to a linear gain, returning 0 at or below `MIN_DB`.
+pub fn db_to_gain(db: f32) -> f32 {
+    if db <= MIN_DB {
+        0.0
+    } else {
+        10f32.powf(db / 20.0)
+    }
+}
+
+/// Scales its input by the linear `level` and by `db` decibels.
 #[derive(Debug, Copy, Clone)]
 pub struct Vca {
     tag: Tag,
@@ -200,12 +421,34 @@ impl Vca {
         Self { tag, wave }
     }
     props!(level, set_level, 0);
+    props!(db, set_db, 1);
+
+    /// Whether the output has gone above 1 in magnitude since the last
+    /// `clear_clipped`, e.g. for a clip light.
+    pub fn clipped(&self, rack: &Rack) -> bool {
+        rack.outputs[(self.tag, 1)] > 0.0
+    }
+
+    pub fn clear_clipped(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 1)] = 0.0;
+    }
 }
 
 impl Signal for Vca {
-    tag!();
+    tag!("level", "db");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn output_names(&self) -> &'static [&'static str] {
+        &["out", "clipped"]
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
-        rack.outputs[(self.tag, 0)] = self.level(rack) * rack.outputs[(self.wave, 0)];
+        let gain = self.level(rack) * db_to_gain(self.db(rack));
+        let out = gain * rack.outputs[(self.wave, 0)];
+        rack.outputs[(self.tag, 0)] = out;
+        if out.abs() > 1.0 {
+            rack.outputs[(self.tag, 1)] = 1.0;
+        }
     }
 }
 
@@ -213,6 +456,7 @@ impl Signal for Vca {
 pub struct VcaBuilder {
     wave: Tag,
     level: Control,
+    db: Control,
 }
 
 impl VcaBuilder {
@@ -220,38 +464,271 @@ impl VcaBuilder {
         Self {
             wave,
             level: 1.0.into(),
+            db: 0.0.into(),
         }
     }
     build!(level);
+    build!(db);
     pub fn rack(&self, rack: &mut Rack) -> Arc<Vca> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.level;
+        rack.controls[(n, 1)] = self.db;
         let vca = Arc::new(Vca::new(n.into(), self.wave));
         rack.push(vca.clone());
         vca
     }
 }
 
+/// Multiplies `wave` by `modulator`, blending the result with the dry signal
+/// by `mix` (1 is fully ring modulated).
+#[derive(Debug, Copy, Clone)]
+pub struct RingModulator {
+    tag: Tag,
+    wave: Tag,
+    modulator: Tag,
+}
+
+impl RingModulator {
+    pub fn new(tag: Tag, wave: Tag, modulator: Tag) -> Self {
+        Self {
+            tag,
+            wave,
+            modulator,
+        }
+    }
+    props!(mix, set_mix, 0);
+}
+
+impl Signal for RingModulator {
+    tag!("mix");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let mix = self.mix(rack);
+        let dry = rack.outputs[(self.wave, 0)];
+        let wet = dry * rack.outputs[(self.modulator, 0)];
+        rack.outputs[(self.tag, 0)] = mix * wet + (1.0 - mix) * dry;
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RingModulatorBuilder {
+    wave: Tag,
+    modulator: Tag,
+    mix: Control,
+}
+
+impl RingModulatorBuilder {
+    pub fn new(wave: Tag, modulator: Tag) -> Self {
+        Self {
+            wave,
+            modulator,
+            mix: 1.0.into(),
+        }
+    }
+    build!(mix);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<RingModulator> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.mix;
+        let rm = Arc::new(RingModulator::new(n.into(), self.wave, self.modulator));
+        rack.push(rm.clone());
+        rm
+    }
+}
+
+/// Amplitude modulation by a bipolar `modulator` that is first shifted to the
+/// range [0, 1], so the signal is never inverted. `depth` is how far the gain
+/// dips below 1.
+#[derive(Debug, Copy, Clone)]
+pub struct AmplitudeModulator {
+    tag: Tag,
+    wave: Tag,
+    modulator: Tag,
+}
+
+impl AmplitudeModulator {
+    pub fn new(tag: Tag, wave: Tag, modulator: Tag) -> Self {
+        Self {
+            tag,
+            wave,
+            modulator,
+        }
+    }
+    props!(depth, set_depth, 0);
+}
+
+impl Signal for AmplitudeModulator {
+    tag!("depth");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let depth = self.depth(rack);
+        let m = 0.5 * (rack.outputs[(self.modulator, 0)] + 1.0);
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)] * (1.0 - depth * (1.0 - m));
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct AmplitudeModulatorBuilder {
+    wave: Tag,
+    modulator: Tag,
+    depth: Control,
+}
+
+impl AmplitudeModulatorBuilder {
+    pub fn new(wave: Tag, modulator: Tag) -> Self {
+        Self {
+            wave,
+            modulator,
+            depth: 1.0.into(),
+        }
+    }
+    build!(depth);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<AmplitudeModulator> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.depth;
+        let am = Arc::new(AmplitudeModulator::new(n.into(), self.wave, self.modulator));
+        rack.push(am.clone());
+        am
+    }
+}
+
+/// Routes `sources` modulation sources to `destinations` outputs. Controls
+/// `0..sources` are the sources, followed by one depth per (source,
+/// destination) pair in source major order. Output `j` is the sum over the
+/// sources of `source[i] * depth[i][j]`.
+#[derive(Debug, Copy, Clone)]
+pub struct ModMatrix {
+    tag: Tag,
+    sources: usize,
+    destinations: usize,
+}
+
+impl ModMatrix {
+    pub fn new<T: Into<Tag>>(tag: T, sources: usize, destinations: usize) -> Self {
+        Self {
+            tag: tag.into(),
+            sources,
+            destinations,
+        }
+    }
+
+    fn depth_index(&self, source: usize, destination: usize) -> usize {
+        self.sources + source * self.destinations + destination
+    }
+
+    pub fn source(&self, rack: &Rack, source: usize) -> f32 {
+        rack.outputs
+            .value(rack.controls[(self.tag, source)])
+            .unwrap()
+    }
+
+    pub fn set_source(&self, rack: &mut Rack, source: usize, value: Control) {
+        rack.controls[(self.tag, source)] = value;
+    }
+
+    pub fn depth(&self, rack: &Rack, source: usize, destination: usize) -> f32 {
+        let i = self.depth_index(source, destination);
+        rack.outputs.value(rack.controls[(self.tag, i)]).unwrap()
+    }
+
+    pub fn set_depth(&self, rack: &mut Rack, source: usize, destination: usize, value: Control) {
+        let i = self.depth_index(source, destination);
+        rack.controls[(self.tag, i)] = value;
+    }
+}
+
+impl Signal for ModMatrix {
+    tag!();
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        for j in 0..self.destinations {
+            rack.outputs[(self.tag, j)] = (0..self.sources)
+                .map(|i| self.source(rack, i) * self.depth(rack, i, j))
+                .sum();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ModMatrixBuilder {
+    sources: Vec<Control>,
+    destinations: usize,
+    depths: Vec<Control>,
+}
+
+impl ModMatrixBuilder {
+    /// Panics if the sources and depths do not fit in `MAX_CONTROLS` or there
+    /// are more than `MAX_OUTPUTS` destinations.
+    pub fn new(sources: Vec<Control>, destinations: usize) -> Self {
+        let m = sources.len();
+        assert!(
+            m + m * destinations <= MAX_CONTROLS && destinations <= MAX_OUTPUTS,
+            "a {m} x {destinations} ModMatrix does not fit in a module"
+        );
+        Self {
+            sources,
+            destinations,
+            depths: vec![0.0.into(); m * destinations],
+        }
+    }
+
+    pub fn depth(&mut self, source: usize, destination: usize, value: Control) -> &mut Self {
+        self.depths[source * self.destinations + destination] = value;
+        self
+    }
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<ModMatrix> {
+        let n = rack.num_modules();
+        for (i, c) in self.sources.iter().chain(self.depths.iter()).enumerate() {
+            rack.controls[(n, i)] = *c;
+        }
+        let mm = Arc::new(ModMatrix::new(n, self.sources.len(), self.destinations));
+        rack.push(mm.clone());
+        mm
+    }
+}
+
+/// Fades from `wave1` (alpha = 0) to `wave2` (alpha = 1). With `equal_power`
+/// the gains follow a quarter sine and cosine so the loudness of uncorrelated
+/// signals stays constant through the fade; otherwise they are linear.
 #[derive(Debug, Copy, Clone)]
 pub struct CrossFade {
     tag: Tag,
     wave1: Tag,
     wave2: Tag,
+    equal_power: bool,
 }
 
 impl CrossFade {
     pub fn new(tag: Tag, wave1: Tag, wave2: Tag) -> Self {
-        Self { tag, wave1, wave2 }
+        Self {
+            tag,
+            wave1,
+            wave2,
+            equal_power: false,
+        }
+    }
+    pub fn equal_power(mut self, value: bool) -> Self {
+        self.equal_power = value;
+        self
     }
     props!(alpha, set_alpha, 0);
 }
 
 impl Signal for CrossFade {
-    tag!();
+    tag!("alpha");
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let alpha = self.alpha(rack);
+        let (g1, g2) = if self.equal_power {
+            let theta = alpha.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+            (theta.cos(), theta.sin())
+        } else {
+            (1.0 - alpha, alpha)
+        };
         rack.outputs[(self.tag, 0)] =
-            alpha * rack.outputs[(self.wave2, 0)] + (1.0 - alpha) * rack.outputs[(self.wave1, 0)];
+            g2 * rack.outputs[(self.wave2, 0)] + g1 * rack.outputs[(self.wave1, 0)];
     }
 }
 
@@ -260,6 +737,7 @@ pub struct CrossFadeBuilder {
     wave1: Tag,
     wave2: Tag,
     alpha: Control,
+    equal_power: bool,
 }
 
 impl CrossFadeBuilder {
@@ -268,18 +746,91 @@ impl CrossFadeBuilder {
             wave1,
             wave2,
             alpha: 0.5.into(),
+            equal_power: false,
         }
     }
     build!(alpha);
+    pub fn equal_power(&mut self, value: bool) -> &mut Self {
+        self.equal_power = value;
+        self
+    }
     pub fn rack(&self, rack: &mut Rack) -> Arc<CrossFade> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.alpha;
-        let cf = Arc::new(CrossFade::new(n.into(), self.wave1, self.wave2));
+        let cf = Arc::new(
+            CrossFade::new(n.into(), self.wave1, self.wave2).equal_power(self.equal_power),
+        );
         rack.push(cf.clone());
         cf
     }
 }
 
+/// A stereo output stage: copies its `left` and `right` sources, given as
+/// (module, output) pairs, to outputs `LEFT` and `RIGHT`. Put it last in the
+/// rack and read both channels with `Rack::stereo`.
+#[derive(Debug, Copy, Clone)]
+pub struct StereoOut {
+    tag: Tag,
+    left: (Tag, usize),
+    right: (Tag, usize),
+}
+
+impl StereoOut {
+    pub fn new<T: Into<Tag>>(tag: T, left: (Tag, usize), right: (Tag, usize)) -> Self {
+        Self {
+            tag: tag.into(),
+            left,
+            right,
+        }
+    }
+    props!(level, set_level, 0);
+}
+
+impl Signal for StereoOut {
+    tag!("level");
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        let level = self.level(rack);
+        rack.outputs[(self.tag, LEFT)] = level * rack.outputs[self.left];
+        rack.outputs[(self.tag, RIGHT)] = level * rack.outputs[self.right];
+    }
+    fn output_names(&self) -> &'static [&'static str] {
+        &["left", "right"]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct StereoOutBuilder {
+    left: (Tag, usize),
+    right: (Tag, usize),
+    level: Control,
+}
+
+impl StereoOutBuilder {
+    pub fn new(left: (Tag, usize), right: (Tag, usize)) -> Self {
+        Self {
+            left,
+            right,
+            level: 1.0.into(),
+        }
+    }
+    /// The same mono signal on both channels.
+    pub fn mono(wave: Tag) -> Self {
+        Self::new((wave, 0), (wave, 0))
+    }
+    /// Both channels of a stereo module.
+    pub fn stereo(wave: Tag) -> Self {
+        Self::new(wave.left(), wave.right())
+    }
+    build!(level);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<StereoOut> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.level;
+        let out = Arc::new(StereoOut::new(n, self.left, self.right));
+        rack.push(out.clone());
+        out
+    }
+}
+
 #[derive(Clone)]
 pub struct Modulator {
     tag: Tag,
@@ -368,47 +919,230 @@ impl ModulatorBuilder {
 pub struct Delay {
     tag: Tag,
     wave: Tag,
+    max_time: f32,
 }
 
 impl Delay {
     pub fn new<T: Into<Tag>>(tag: T, wave: Tag) -> Self {
+        Self::with_max_time(tag, wave, DEFAULT_MAX_DELAY)
+    }
+
+    /// A delay whose buffer holds `max_time` seconds, longer delay times are
+    /// clamped to it.
+    pub fn with_max_time<T: Into<Tag>>(tag: T, wave: Tag, max_time: f32) -> Self {
         Delay {
             tag: tag.into(),
             wave,
+            max_time,
         }
     }
     props!(delay, set_delay, 0);
+
+    pub fn max_time(&self) -> f32 {
+        self.max_time
+    }
 }
 
 impl Signal for Delay {
-    tag!();
+    tag!("delay");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
-        let val = rack.outputs[(self.wave, 0)];
+        let val = zap_denormal(rack.outputs[(self.wave, 0)]);
         let d = self.delay(rack) * sample_rate;
-        rack.buffers.buffers_mut(self.tag).push(val);
-        rack.outputs[(self.tag, 0)] = rack.buffers.buffers(self.tag).get_cubic(d);
+        let buffer = rack.buffers.buffers_mut(self.tag);
+        // Leave room for the samples either side of the read.
+        let d = d.clamp(0.0, buffer.len() as f32 - 2.0);
+        buffer.push(val);
+        let out = buffer.get_interpolated_with_state(d, &mut rack.state[(self.tag, 0)]);
+        rack.outputs[(self.tag, 0)] = zap_denormal(out);
+    }
+    fn set_sample_rate(&self, rack: &mut Rack, sample_rate: f32) {
+        rack.buffers
+            .buffers_mut(self.tag)
+            .resize(delay_len(self.max_time, sample_rate));
     }
 }
 
+/// Maximum delay time in seconds of a `Delay` unless set otherwise.
+pub const DEFAULT_MAX_DELAY: f32 = 1.0;
+
+pub(crate) fn delay_len(max_time: f32, sample_rate: f32) -> usize {
+    ((max_time * sample_rate).ceil() as usize).max(4)
+}
+
 pub struct DelayBuilder {
     wave: Tag,
     delay: Control,
+    max_time: f32,
+    interpolation: InterpolationMode,
 }
 
 impl DelayBuilder {
     pub fn new(wave: Tag, delay: Control) -> Self {
-        Self { wave, delay }
+        Self {
+            wave,
+            delay,
+            max_time: DEFAULT_MAX_DELAY,
+            interpolation: InterpolationMode::default(),
+        }
     }
 
     build!(delay);
 
+    /// How the delay line is read between samples. Linear is cheapest.
+    pub fn interpolation(&mut self, value: InterpolationMode) -> &mut Self {
+        self.interpolation = value;
+        self
+    }
+
+    /// Longest delay in seconds the buffer has room for.
+    pub fn max_time(&mut self, value: f32) -> &mut Self {
+        self.max_time = value;
+        self
+    }
+
     pub fn rack(&mut self, rack: &mut Rack) -> Arc<Delay> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.delay;
-        let delay = Arc::new(Delay::new(n, self.wave));
-        rack.buffers
-            .set_buffer(delay.tag(), RingBuffer::new32(44100.0));
+        let delay = Arc::new(Delay::with_max_time(n, self.wave, self.max_time));
+        rack.buffers.set_buffer(
+            delay.tag(),
+            RingBuffer::new(0, vec![0.0; delay_len(self.max_time, 44_100.0)])
+                .with_interpolation(self.interpolation),
+        );
         rack.push(delay.clone());
         delay
     }
 }
+
+/// Wraps a whole `Rack` as a single module. Control `i` of the `SubRack` is
+/// copied into the inner control `inputs[i]` and output `j` is read from the
+/// inner output `outputs[j]`, both given as (module, index) pairs of the inner
+/// rack. The inner rack is played once per sample, unless it is locked through
+/// `inner` at the time, in which case the outputs keep their previous values.
+pub struct SubRack {
+    tag: Tag,
+    inner: Mutex<Rack>,
+    inputs: Vec<(Tag, usize)>,
+    outputs: Vec<(Tag, usize)>,
+}
+
+impl SubRack {
+    pub fn new<T: Into<Tag>>(
+        tag: T,
+        inner: Rack,
+        inputs: Vec<(Tag, usize)>,
+        outputs: Vec<(Tag, usize)>,
+    ) -> Self {
+        Self {
+            tag: tag.into(),
+            inner: Mutex::new(inner),
+            inputs,
+            outputs,
+        }
+    }
+
+    pub fn input(&self, rack: &Rack, index: usize) -> f32 {
+        rack.outputs
+            .value(rack.controls[(self.tag, index)])
+            .unwrap()
+    }
+
+    pub fn set_input(&self, rack: &mut Rack, index: usize, value: Control) {
+        rack.controls[(self.tag, index)] = value;
+    }
+
+    /// Access the inner rack, e.g. to call methods of its modules. The inner
+    /// rack is skipped while the guard is alive, so don't hold on to it.
+    pub fn inner(&self) -> parking_lot::MutexGuard<'_, Rack> {
+        self.inner.lock()
+    }
+}
+
+impl Signal for SubRack {
+    tag!();
+
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let Some(mut inner) = self.inner.try_lock() else {
+            return;
+        };
+        for (i, to) in self.inputs.iter().enumerate() {
+            inner.controls[*to] = self.input(rack, i).into();
+        }
+        inner.play(sample_rate);
+        for (j, from) in self.outputs.iter().enumerate() {
+            rack.outputs[(self.tag, j)] = inner.outputs[*from] * inner.fade_gain();
+        }
+    }
+
+    fn reset(&self, _rack: &mut Rack) {
+        self.inner.lock().reset();
+    }
+
+    fn duplicate(&self) -> Option<Arc<dyn Signal + Send + Sync>> {
+        Some(Arc::new(SubRack::new(
+            self.tag,
+            self.inner.lock().clone(),
+            self.inputs.clone(),
+            self.outputs.clone(),
+        )))
+    }
+
+    fn set_sample_rate(&self, _rack: &mut Rack, sample_rate: f32) {
+        self.inner.lock().set_sample_rate(sample_rate);
+    }
+}
+
+pub struct SubRackBuilder {
+    inner: Rack,
+    inputs: Vec<(Tag, usize)>,
+    outputs: Vec<(Tag, usize)>,
+    controls: Vec<Control>,
+}
+
+impl SubRackBuilder {
+    /// The initial value of each input is the value of the inner control it
+    /// replaces.
+    pub fn new(inner: Rack, inputs: &[(Tag, usize)], outputs: &[(Tag, usize)]) -> Self {
+        let controls = inputs
+            .iter()
+            .map(|to| {
+                inner
+                    .outputs
+                    .value(inner.controls[*to])
+                    .unwrap_or(0.0)
+                    .into()
+            })
+            .collect();
+        Self {
+            inner,
+            inputs: inputs.to_vec(),
+            outputs: outputs.to_vec(),
+            controls,
+        }
+    }
+
+    pub fn input(&mut self, index: usize, value: Control) -> &mut Self {
+        self.controls[index] = value;
+        self
+    }
+
+    /// Moves the inner rack into the new module, leaving an empty rack behind.
+    pub fn rack(&mut self, rack: &mut Rack) -> Arc<SubRack> {
+        let n = rack.num_modules();
+        for (i, c) in self.controls.iter().enumerate() {
+            rack.controls[(n, i)] = *c;
+        }
+        let inner = std::mem::take(&mut self.inner);
+        let sub = Arc::new(SubRack::new(
+            n,
+            inner,
+            self.inputs.clone(),
+            self.outputs.clone(),
+        ));
+        rack.push(sub.clone());
+        sub
+    }
+}
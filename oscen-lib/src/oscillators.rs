@@ -1,4 +1,5 @@
 use crate::rack::*;
+use crate::utils::{pink_filter, Xorshift32, PINK_STATE};
 use crate::{build, props, tag};
 use math::round::floor;
 use rand::prelude::*;
@@ -14,14 +15,24 @@ pub struct OscBuilder {
     hz: Control,
     amplitude: Control,
     arg: Control,
+    phase_mod: Control,
+    phase_output: bool,
+    sync_output: bool,
 }
 
 /// A standard oscillator that has phase, hz, and amp. Pass in a signal function
-/// to operate on the phase and an optional extra argument.
+/// to operate on the phase and an optional extra argument. `phase_mod` is added
+/// to the phase (in cycles) before the signal function is applied, for phase
+/// modulation. With `phase_output` enabled output 1 is the phase in [0, 1),
+/// e.g. to sync a scope or other modules to the oscillator's cycle. With
+/// `sync_output` enabled output 2 is 1 on the first sample of each cycle, after
+/// the phase wraps, and 0 otherwise.
 #[derive(Clone)]
 pub struct Oscillator {
     tag: Tag,
     signal_fn: fn(f32, f32) -> f32,
+    phase_output: bool,
+    sync_output: bool,
 }
 
 impl OscBuilder {
@@ -32,6 +43,9 @@ impl OscBuilder {
             hz: 0.0.into(),
             amplitude: 1.0.into(),
             arg: 0.5.into(),
+            phase_mod: 0.0.into(),
+            phase_output: false,
+            sync_output: false,
         }
     }
 
@@ -40,17 +54,34 @@ impl OscBuilder {
         self
     }
 
+    /// Publish the phase as output 1.
+    pub fn phase_output(&mut self, value: bool) -> &mut Self {
+        self.phase_output = value;
+        self
+    }
+
+    /// Publish a pulse at the start of each cycle as output 2.
+    pub fn sync_output(&mut self, value: bool) -> &mut Self {
+        self.sync_output = value;
+        self
+    }
+
     build!(hz);
     build!(amplitude);
     build!(arg);
+    build!(phase_mod);
 
     pub fn rack(&self, rack: &mut Rack) -> Arc<Oscillator> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.hz;
         rack.controls[(n, 1)] = self.amplitude;
         rack.controls[(n, 2)] = self.arg;
+        rack.controls[(n, 3)] = self.phase_mod;
         rack.state[(n, 0)] = self.phase;
-        let osc = Arc::new(Oscillator::new(n, self.signal_fn));
+        let mut osc = Oscillator::new(n, self.signal_fn);
+        osc.phase_output = self.phase_output;
+        osc.sync_output = self.sync_output;
+        let osc = Arc::new(osc);
         rack.push(osc.clone());
         osc
     }
@@ -90,6 +121,8 @@ impl Oscillator {
         Self {
             tag: tag.into(),
             signal_fn,
+            phase_output: false,
+            sync_output: false,
         }
     }
     pub fn phase(&self, state: &State) -> f32 {
@@ -101,24 +134,47 @@ impl Oscillator {
     props!(hz, set_hz, 0);
     props!(amplitude, set_amplitude, 1);
     props!(arg, set_arg, 2);
+    props!(phase_mod, set_phase_mod, 3);
 }
 
 impl Signal for Oscillator {
-    tag!();
+    tag!("hz", "amplitude", "arg", "phase_mod");
+    fn output_names(&self) -> &'static [&'static str] {
+        match (self.phase_output, self.sync_output) {
+            (true, true) => &["out", "phase", "sync"],
+            (true, false) => &["out", "phase"],
+            // Output 1 is left unnamed as nothing writes it.
+            (false, true) => &["out", "", "sync"],
+            (false, false) => &["out"],
+        }
+    }
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let phase = self.phase(&rack.state);
         let hz = self.hz(rack);
         let amp = self.amplitude(rack);
         let arg = self.arg(rack);
+        let pm = self.phase_mod(rack);
         let mut ph = phase + hz / sample_rate;
+        let mut wrapped = false;
         while ph >= 1.0 {
-            ph -= 1.0
+            ph -= 1.0;
+            wrapped = true;
         }
         while ph <= -1.0 {
-            ph += 1.0
+            ph += 1.0;
+            wrapped = true;
         }
         self.set_phase(&mut rack.state, ph);
-        rack.outputs[(self.tag, 0)] = amp * (self.signal_fn)(phase, arg);
+        rack.outputs[(self.tag, 0)] = amp * (self.signal_fn)((phase + pm) % 1.0, arg);
+        if self.phase_output {
+            rack.outputs[(self.tag, 1)] = phase.rem_euclid(1.0);
+        }
+        if self.sync_output {
+            // The wrap happens while advancing, so the pulse is written on the
+            // next sample, the first of the new cycle.
+            rack.outputs[(self.tag, 2)] = rack.state[(self.tag, 1)];
+            rack.state[(self.tag, 1)] = if wrapped { 1.0 } else { 0.0 };
+        }
     }
 }
 
@@ -155,12 +211,64 @@ impl Const {
 }
 
 impl Signal for Const {
-    tag!();
+    tag!("value");
+    fn is_pure(&self) -> bool {
+        true
+    }
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         rack.outputs[(self.tag, 0)] = self.value(rack);
     }
 }
 
+/// Outputs a value fixed when the module is built. Unlike `Const` it has no
+/// control, so the value cannot be changed or patched, which makes it clear
+/// that e.g. an offset is not meant to move.
+#[derive(Debug, Copy, Clone)]
+pub struct Constant {
+    tag: Tag,
+    value: f32,
+}
+
+impl Constant {
+    pub fn new<T: Into<Tag>>(tag: T, value: f32) -> Self {
+        Self {
+            tag: tag.into(),
+            value,
+        }
+    }
+
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl Signal for Constant {
+    tag!();
+    fn is_pure(&self) -> bool {
+        true
+    }
+    fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
+        rack.outputs[(self.tag, 0)] = self.value;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ConstantBuilder {
+    value: f32,
+}
+
+impl ConstantBuilder {
+    pub fn new(value: f32) -> Self {
+        Self { value }
+    }
+    pub fn rack(&self, rack: &mut Rack) -> Arc<Constant> {
+        let n = rack.num_modules();
+        let out = Arc::new(Constant::new(n, self.value));
+        rack.push(out.clone());
+        out
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum NoiseDistribution {
     StdNormal,
@@ -178,6 +286,7 @@ pub struct WhiteNoise {
 pub struct WhiteNoiseBuilder {
     amplitude: Control,
     dist: NoiseDistribution,
+    seed: Option<u64>,
 }
 
 impl Default for WhiteNoiseBuilder {
@@ -185,6 +294,7 @@ impl Default for WhiteNoiseBuilder {
         Self {
             amplitude: 1.0.into(),
             dist: NoiseDistribution::StdNormal,
+            seed: None,
         }
     }
 }
@@ -197,10 +307,17 @@ impl WhiteNoiseBuilder {
         self.dist = arg;
         self
     }
+    /// Seed the noise, overriding the seed of the rack.
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
     build!(amplitude);
     pub fn rack(&self, rack: &mut Rack) -> Arc<WhiteNoise> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.amplitude;
+        let seed = self.seed.unwrap_or_else(|| rack.module_seed(n));
+        rack.state[(n, 0)] = Xorshift32::new(seed).to_state();
         let noise = Arc::new(WhiteNoise::new(n, self.dist));
         rack.push(noise.clone());
         noise
@@ -218,20 +335,24 @@ impl WhiteNoise {
 }
 
 impl Signal for WhiteNoise {
-    tag!();
+    tag!("amplitude");
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let amplitude = self.amplitude(rack);
-        let mut rng = thread_rng();
+        let mut rng = Xorshift32::from_state(rack.state[(self.tag, 0)]);
         let out = match self.dist {
             NoiseDistribution::Uni => {
                 amplitude * Uniform::new_inclusive(-1.0, 1.0).sample(&mut rng)
             }
             NoiseDistribution::StdNormal => amplitude * rng.sample::<f32, _>(StandardNormal),
         };
+        rack.state[(self.tag, 0)] = rng.to_state();
         rack.outputs[(self.tag, 0)] = out;
     }
 }
 
+/// State slot of the `PinkNoise` random number generator, after the filter.
+const PINK_RNG: usize = PINK_STATE;
+
 #[derive(Copy, Clone)]
 pub struct PinkNoise {
     tag: Tag,
@@ -240,12 +361,14 @@ pub struct PinkNoise {
 #[derive(Copy, Clone)]
 pub struct PinkNoiseBuilder {
     amplitude: Control,
+    seed: Option<u64>,
 }
 
 impl Default for PinkNoiseBuilder {
     fn default() -> Self {
         Self {
             amplitude: 1.0.into(),
+            seed: None,
         }
     }
 }
@@ -262,9 +385,16 @@ impl PinkNoiseBuilder {
         Self::default()
     }
     build!(amplitude);
+    /// Seed the noise, overriding the seed of the rack.
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
     pub fn rack(&self, rack: &mut Rack) -> Arc<PinkNoise> {
         let n = rack.num_modules();
         rack.controls[(n, 0)] = self.amplitude;
+        let seed = self.seed.unwrap_or_else(|| rack.module_seed(n));
+        rack.state[(n, PINK_RNG)] = Xorshift32::new(seed).to_state();
         let noise = Arc::new(PinkNoise::new(n));
         rack.push(noise.clone());
         noise
@@ -272,27 +402,14 @@ impl PinkNoiseBuilder {
 }
 
 impl Signal for PinkNoise {
-    tag!();
+    tag!("amplitude");
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let tag = self.tag;
         let amplitude = self.amplitude(rack);
-        let mut rng = thread_rng();
+        let mut rng = Xorshift32::from_state(rack.state[(tag, PINK_RNG)]);
         let white = Uniform::new_inclusive(-1.0, 1.0).sample(&mut rng);
-        rack.state[(tag, 0)] = 0.99886 * rack.state[(tag, 0)] + white * 0.0555179;
-        rack.state[(tag, 1)] = 0.99332 * rack.state[(tag, 1)] + white * 0.0750759;
-        rack.state[(tag, 2)] = 0.969 * rack.state[(tag, 2)] + white * 0.153852;
-        rack.state[(tag, 3)] = 0.8665 * rack.state[(tag, 3)] + white * 0.3104856;
-        rack.state[(tag, 4)] = 0.55 * rack.state[(tag, 4)] + white * 0.5329522;
-        rack.state[(tag, 5)] = -0.7616 * rack.state[(tag, 5)] - white * 0.016898;
-        let pink = rack.state[(tag, 0)]
-            + rack.state[(tag, 1)]
-            + rack.state[(tag, 2)]
-            + rack.state[(tag, 3)]
-            + rack.state[(tag, 4)]
-            + rack.state[(tag, 5)]
-            + rack.state[(tag, 6)]
-            + white * 0.5362;
-        rack.state[(tag, 6)] = white * 0.115926;
+        rack.state[(tag, PINK_RNG)] = rng.to_state();
+        let pink = pink_filter(&mut rack.state.state_mut(tag)[..PINK_STATE], white);
         rack.outputs[(self.tag, 0)] = pink * amplitude;
     }
 }
@@ -364,7 +481,7 @@ fn sinc(x: f32) -> f32 {
 }
 
 impl Signal for FourierOsc {
-    tag!();
+    tag!("hz", "amplitude");
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let tag = self.tag;
         let hz = self.hz(rack);
@@ -446,7 +563,7 @@ impl Clock {
 }
 
 impl Signal for Clock {
-    tag!();
+    tag!("interval");
     fn signal(&self, rack: &mut Rack, sample_rate: f32) {
         let tag = self.tag;
         let interval = self.interval(rack) * sample_rate;
@@ -463,3 +580,427 @@ impl Signal for Clock {
         rack.outputs[(self.tag, 0)] = out;
     }
 }
+
+/// Frequency offsets of the seven supersaw voices at full detune, relative
+/// to the center frequency.
+const SUPERSAW_OFFSETS: [f32; 7] = [
+    -0.11002313,
+    -0.06288439,
+    -0.01952356,
+    0.0,
+    0.01991221,
+    0.06216538,
+    0.10745242,
+];
+
+/// Seven detuned sawtooth oscillators in the style of the Roland JP-8000,
+/// following Adam Szabo's analysis. `detune` (0 to 1) spreads the side voices
+/// along the measured, non-linear detune curve and `mix` (0 to 1) fades from
+/// the center voice to the side voices.
+#[derive(Copy, Clone)]
+pub struct SuperSaw {
+    tag: Tag,
+}
+
+impl SuperSaw {
+    pub fn new<T: Into<Tag>>(tag: T) -> Self {
+        Self { tag: tag.into() }
+    }
+    props!(hz, set_hz, 0);
+    props!(detune, set_detune, 1);
+    props!(mix, set_mix, 2);
+    props!(amplitude, set_amplitude, 3);
+}
+
+fn supersaw_detune(x: f32) -> f32 {
+    const COEFFICIENTS: [f32; 12] = [
+        10028.731,
+        -50818.867,
+        111363.48,
+        -138150.67,
+        106649.67,
+        -53046.965,
+        17019.951,
+        -3425.0837,
+        404.2704,
+        -24.187883,
+        0.6717418,
+        0.0030115596,
+    ];
+    COEFFICIENTS.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+impl Signal for SuperSaw {
+    tag!("hz", "detune", "mix", "amplitude");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let hz = self.hz(rack);
+        let detune = supersaw_detune(self.detune(rack).clamp(0.0, 1.0));
+        let mix = self.mix(rack).clamp(0.0, 1.0);
+        let center = -0.55366 * mix + 0.99785;
+        let side = -0.73764 * mix * mix + 1.2841 * mix + 0.044372;
+        let mut out = 0.0;
+        for (i, offset) in SUPERSAW_OFFSETS.iter().enumerate() {
+            let phase = rack.state[(self.tag, i)];
+            let gain = if i == 3 { center } else { side };
+            out += gain * saw_osc(phase, 0.0);
+            let ph = phase + hz * (1.0 + offset * detune) / sample_rate;
+            rack.state[(self.tag, i)] = ph - ph.floor();
+        }
+        rack.outputs[(self.tag, 0)] = self.amplitude(rack) * out;
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct SuperSawBuilder {
+    hz: Control,
+    detune: Control,
+    mix: Control,
+    amplitude: Control,
+    random_phases: bool,
+    seed: Option<u64>,
+}
+
+impl Default for SuperSawBuilder {
+    fn default() -> Self {
+        Self {
+            hz: 0.0.into(),
+            detune: 0.5.into(),
+            mix: 0.5.into(),
+            amplitude: 1.0.into(),
+            random_phases: true,
+            seed: None,
+        }
+    }
+}
+
+impl SuperSawBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    build!(hz);
+    build!(detune);
+    build!(mix);
+    build!(amplitude);
+    /// Start each voice at a random phase (the default), as the free running
+    /// oscillators of the original do, rather than all at 0.
+    pub fn random_phases(&mut self, value: bool) -> &mut Self {
+        self.random_phases = value;
+        self
+    }
+    /// Seed the random phases, overriding the seed of the rack.
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
+    pub fn rack(&self, rack: &mut Rack) -> Arc<SuperSaw> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        rack.controls[(n, 1)] = self.detune;
+        rack.controls[(n, 2)] = self.mix;
+        rack.controls[(n, 3)] = self.amplitude;
+        if self.random_phases {
+            let seed = self.seed.unwrap_or_else(|| rack.module_seed(n));
+            let mut rng = Xorshift32::new(seed);
+            for i in 0..SUPERSAW_OFFSETS.len() {
+                rack.state[(n, i)] = rng.gen();
+            }
+        }
+        let saw = Arc::new(SuperSaw::new(n));
+        rack.push(saw.clone());
+        saw
+    }
+}
+
+/// The waveform of a `PolyBlepOsc`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BlepWaveform {
+    #[default]
+    Saw,
+    /// A square wave that is high for `pulse_width` of each cycle.
+    Pulse,
+    /// One band-limited impulse per cycle, whose samples sum to 1.
+    Impulse,
+}
+
+/// The polynomial band-limited step residual for a discontinuity at phase 0,
+/// where `t` is the phase in [0, 1) and `dt` the phase increment per sample.
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Oscillator whose discontinuities are smoothed with PolyBLEP corrections,
+/// so it aliases far less than `saw_osc` and `square_osc`. The `pulse_width`
+/// of the `Pulse` waveform (0 to 1) is read every sample, with the falling
+/// edge corrected where it is, so it can be swept by an LFO for PWM. The
+/// `Impulse` train is the difference of successive samples of the corrected
+/// saw, e.g. to excite a formant filter.
+#[derive(Debug, Copy, Clone)]
+pub struct PolyBlepOsc {
+    tag: Tag,
+    waveform: BlepWaveform,
+}
+
+impl PolyBlepOsc {
+    pub fn new<T: Into<Tag>>(tag: T, waveform: BlepWaveform) -> Self {
+        Self {
+            tag: tag.into(),
+            waveform,
+        }
+    }
+    props!(hz, set_hz, 0);
+    props!(amplitude, set_amplitude, 1);
+    props!(pulse_width, set_pulse_width, 2);
+
+    pub fn waveform(&self) -> BlepWaveform {
+        self.waveform
+    }
+}
+
+impl Signal for PolyBlepOsc {
+    tag!("hz", "amplitude", "pulse_width");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let t = rack.state[(self.tag, 0)];
+        let dt = (self.hz(rack) / sample_rate).abs().min(0.5);
+        let out = match self.waveform {
+            BlepWaveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            BlepWaveform::Pulse => {
+                // Keep both edges at least a sample apart.
+                let width = self.pulse_width(rack).clamp(dt, 1.0 - dt);
+                let naive = if t < width { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t - width).rem_euclid(1.0), dt)
+            }
+            BlepWaveform::Impulse => {
+                let saw = 2.0 * t - 1.0 - poly_blep(t, dt);
+                let prev = rack.state[(self.tag, 1)];
+                rack.state[(self.tag, 1)] = saw;
+                // The saw rises by 2 per cycle and falls by 2 at each step.
+                (2.0 * dt - (saw - prev)) / 2.0
+            }
+        };
+        let ph = t + dt;
+        rack.state[(self.tag, 0)] = ph - ph.floor();
+        rack.outputs[(self.tag, 0)] = self.amplitude(rack) * out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PolyBlepOscBuilder {
+    waveform: BlepWaveform,
+    phase: f32,
+    hz: Control,
+    amplitude: Control,
+    pulse_width: Control,
+}
+
+impl PolyBlepOscBuilder {
+    pub fn new(waveform: BlepWaveform) -> Self {
+        Self {
+            waveform,
+            phase: 0.0,
+            hz: 0.0.into(),
+            amplitude: 1.0.into(),
+            pulse_width: 0.5.into(),
+        }
+    }
+    pub fn phase(&mut self, value: f32) -> &mut Self {
+        self.phase = value;
+        self
+    }
+    build!(hz);
+    build!(amplitude);
+    build!(pulse_width);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<PolyBlepOsc> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        rack.controls[(n, 1)] = self.amplitude;
+        rack.controls[(n, 2)] = self.pulse_width;
+        rack.state[(n, 0)] = self.phase.rem_euclid(1.0);
+        // The saw the impulse train is taken from, just before `phase`.
+        rack.state[(n, 1)] = 1.0 - 2.0 * (1.0 - self.phase).rem_euclid(1.0);
+        let osc = Arc::new(PolyBlepOsc::new(n, self.waveform));
+        rack.push(osc.clone());
+        osc
+    }
+}
+
+/// Additive oscillator summing sine partials, e.g. for organ and bell tones.
+/// Control 0 is `hz` and control `1 + i` the amplitude of partial `i`, whose
+/// frequency is `hz` times its ratio. Ratios default to the harmonic series
+/// 1, 2, 3, ... and can be made inharmonic or detuned with `ratios`. Partials
+/// at or above the Nyquist frequency are skipped.
+#[derive(Clone)]
+pub struct AdditiveOsc {
+    tag: Tag,
+    ratios: Vec<f32>,
+}
+
+impl AdditiveOsc {
+    pub fn new<T: Into<Tag>>(tag: T, ratios: Vec<f32>) -> Self {
+        assert!(
+            ratios.len() < MAX_CONTROLS && ratios.len() <= MAX_STATE,
+            "too many partials for one module"
+        );
+        Self {
+            tag: tag.into(),
+            ratios,
+        }
+    }
+    props!(hz, set_hz, 0);
+
+    pub fn partials(&self) -> usize {
+        self.ratios.len()
+    }
+
+    pub fn amplitude(&self, rack: &Rack, partial: usize) -> f32 {
+        rack.outputs
+            .value(rack.controls[(self.tag, 1 + partial)])
+            .unwrap()
+    }
+
+    pub fn set_amplitude(&self, rack: &mut Rack, partial: usize, value: Control) {
+        rack.controls[(self.tag, 1 + partial)] = value;
+    }
+}
+
+impl Signal for AdditiveOsc {
+    tag!();
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let hz = self.hz(rack);
+        let nyquist = sample_rate / 2.0;
+        let mut out = 0.0;
+        for (i, ratio) in self.ratios.iter().enumerate() {
+            let f = hz * ratio;
+            if f.abs() >= nyquist {
+                continue;
+            }
+            let phase = rack.state[(self.tag, i)];
+            out += self.amplitude(rack, i) * (phase * TAU).sin();
+            let ph = phase + f / sample_rate;
+            rack.state[(self.tag, i)] = ph - ph.floor();
+        }
+        rack.outputs[(self.tag, 0)] = out;
+    }
+}
+
+#[derive(Clone)]
+pub struct AdditiveOscBuilder {
+    hz: Control,
+    amplitudes: Vec<Control>,
+    ratios: Vec<f32>,
+}
+
+impl AdditiveOscBuilder {
+    /// An oscillator with one partial per amplitude.
+    pub fn new(amplitudes: &[Control]) -> Self {
+        Self {
+            hz: 0.0.into(),
+            amplitudes: amplitudes.to_vec(),
+            ratios: (1..=amplitudes.len()).map(|h| h as f32).collect(),
+        }
+    }
+    build!(hz);
+    /// Frequency ratio of each partial to `hz`, one per amplitude.
+    pub fn ratios(&mut self, value: &[f32]) -> &mut Self {
+        assert_eq!(value.len(), self.amplitudes.len());
+        self.ratios = value.to_vec();
+        self
+    }
+    pub fn rack(&self, rack: &mut Rack) -> Arc<AdditiveOsc> {
+        let n = rack.num_modules();
+        let osc = Arc::new(AdditiveOsc::new(n, self.ratios.clone()));
+        rack.controls[(n, 0)] = self.hz;
+        for (i, a) in self.amplitudes.iter().enumerate() {
+            rack.controls[(n, 1 + i)] = *a;
+        }
+        rack.push(osc.clone());
+        osc
+    }
+}
+
+/// Sine operator for DX style FM synthesis, running at `hz` times `ratio`.
+/// `phase_mod` is added to the phase in cycles, so patching the output of one
+/// operator into the `phase_mod` of another builds an algorithm. `feedback`
+/// feeds the operator's own output back into its phase, averaged over the last
+/// two samples to keep it from oscillating. `level` scales the output; patch
+/// it from an `Adsr` for an enveloped operator.
+#[derive(Copy, Clone)]
+pub struct FmOperator {
+    tag: Tag,
+}
+
+impl FmOperator {
+    pub fn new<T: Into<Tag>>(tag: T) -> Self {
+        Self { tag: tag.into() }
+    }
+    props!(hz, set_hz, 0);
+    props!(ratio, set_ratio, 1);
+    props!(phase_mod, set_phase_mod, 2);
+    props!(feedback, set_feedback, 3);
+    props!(level, set_level, 4);
+}
+
+impl Signal for FmOperator {
+    tag!("hz", "ratio", "phase_mod", "feedback", "level");
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let tag = self.tag;
+        let phase = rack.state[(tag, 0)];
+        let fb = self.feedback(rack) * 0.5 * (rack.state[(tag, 1)] + rack.state[(tag, 2)]);
+        let out = self.level(rack) * ((phase + self.phase_mod(rack) + fb) * TAU).sin();
+        rack.state[(tag, 2)] = rack.state[(tag, 1)];
+        rack.state[(tag, 1)] = out;
+        let ph = phase + self.hz(rack) * self.ratio(rack) / sample_rate;
+        rack.state[(tag, 0)] = ph - ph.floor();
+        rack.outputs[(tag, 0)] = out;
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct FmOperatorBuilder {
+    hz: Control,
+    ratio: Control,
+    phase_mod: Control,
+    feedback: Control,
+    level: Control,
+}
+
+impl Default for FmOperatorBuilder {
+    fn default() -> Self {
+        Self {
+            hz: 0.0.into(),
+            ratio: 1.0.into(),
+            phase_mod: 0.0.into(),
+            feedback: 0.0.into(),
+            level: 1.0.into(),
+        }
+    }
+}
+
+impl FmOperatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    build!(hz);
+    build!(ratio);
+    build!(phase_mod);
+    build!(feedback);
+    build!(level);
+    pub fn rack(&self, rack: &mut Rack) -> Arc<FmOperator> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.hz;
+        rack.controls[(n, 1)] = self.ratio;
+        rack.controls[(n, 2)] = self.phase_mod;
+        rack.controls[(n, 3)] = self.feedback;
+        rack.controls[(n, 4)] = self.level;
+        let op = Arc::new(FmOperator::new(n));
+        rack.push(op.clone());
+        op
+    }
+}
@@ -1,5 +1,7 @@
+use crate::utils::{hermite, lagrange};
 use arr_macro::arr;
-use std::ops::{Index, IndexMut};
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut, Mul};
 use std::sync::Arc;
 
 pub type SignalFn = fn(f32, f32) -> f32;
@@ -10,8 +12,13 @@ pub const MAX_STATE: usize = 64;
 // Must be changed by hand in Buffers due to limitaion of arr! marcro
 pub const MAX_MODULES: usize = 1024;
 
+/// Stereo modules write the left channel to output `LEFT` and the right
+/// channel to output `RIGHT`.
+pub const LEFT: usize = 0;
+pub const RIGHT: usize = 1;
+
 /// Unique identifier for each Synth Module.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Tag(pub usize);
 
 impl Tag {
@@ -21,6 +28,14 @@ impl Tag {
     fn get(&self) -> usize {
         self.0
     }
+    /// The left channel of a stereo module, as a (module, output) pair.
+    pub fn left(self) -> (Tag, usize) {
+        (self, LEFT)
+    }
+    /// The right channel of a stereo module, as a (module, output) pair.
+    pub fn right(self) -> (Tag, usize) {
+        (self, RIGHT)
+    }
 }
 
 impl From<Tag> for usize {
@@ -38,12 +53,21 @@ impl From<usize> for Tag {
 #[derive(Debug, Copy, Clone)]
 pub enum Control {
     V(Tag, usize),
+    /// Like `V` but the output is multiplied by the scale factor.
+    S(Tag, usize, f32),
     F(f32),
     B(bool),
     I(usize),
 }
 
 impl Control {
+    /// The (module, output) pair this control is patched to, if any.
+    pub fn source(&self) -> Option<(Tag, usize)> {
+        match self {
+            Control::V(t, i) | Control::S(t, i, _) => Some((*t, *i)),
+            _ => None,
+        }
+    }
     pub fn idx(&self) -> usize {
         match self {
             Control::I(u) => *u,
@@ -76,6 +100,14 @@ impl From<Tag> for Control {
     }
 }
 
+/// Output 0 of a module scaled by a constant, e.g. `osc.tag() * 0.5`.
+impl Mul<f32> for Tag {
+    type Output = Control;
+    fn mul(self, rhs: f32) -> Self::Output {
+        Control::S(self, 0, rhs)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Controls([[Control; MAX_CONTROLS]; MAX_MODULES]);
 
@@ -144,6 +176,7 @@ impl Outputs {
         match ctrl {
             Control::F(p) => Some(p),
             Control::V(n, i) => Some(self.0[n.get()][i]),
+            Control::S(n, i, k) => Some(self.0[n.get()][i] * k),
             _ => None,
         }
     }
@@ -221,11 +254,28 @@ where
         &mut self.state_mut(index.0.into())[index.1]
     }
 }
+/// How `RingBuffer::get_interpolated` reads between samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum InterpolationMode {
+    /// Straight line between the two nearest samples. Cheapest.
+    Linear,
+    /// 4-point, 3rd order Lagrange polynomial, as `get_lagrange`.
+    Lagrange,
+    /// 4-point, 3rd order Hermite (Catmull-Rom) polynomial, as `get_cubic`.
+    #[default]
+    Hermite4,
+    /// First order allpass, see `get_allpass`. Flat magnitude response and
+    /// exact fractional delay, which keeps tuned delay lines in pitch. It
+    /// needs state kept by the reader, so `get_interpolated` reads linearly.
+    Allpass,
+}
+
 /// Circular buffer
 #[derive(Clone)]
 pub struct RingBuffer<T = f32> {
     buffer: Vec<T>,
     write_pos: usize,
+    interpolation: InterpolationMode,
 }
 
 impl<T> RingBuffer<T>
@@ -233,7 +283,24 @@ where
     T: Clone + Default,
 {
     pub fn new(write_pos: usize, buffer: Vec<T>) -> Self {
-        Self { buffer, write_pos }
+        Self {
+            buffer,
+            write_pos,
+            interpolation: InterpolationMode::default(),
+        }
+    }
+
+    pub fn with_interpolation(mut self, mode: InterpolationMode) -> Self {
+        self.interpolation = mode;
+        self
+    }
+
+    pub fn interpolation(&self) -> InterpolationMode {
+        self.interpolation
+    }
+
+    pub fn set_interpolation(&mut self, mode: InterpolationMode) {
+        self.interpolation = mode;
     }
 
     pub fn push(&mut self, v: T) {
@@ -245,6 +312,11 @@ where
         self.buffer.len()
     }
 
+    /// Set every element of the buffer back to its default.
+    pub fn clear(&mut self) {
+        self.buffer.fill(Default::default());
+    }
+
     pub fn set_write_pos(&mut self, wp: usize) {
         self.write_pos = wp % self.buffer.len();
     }
@@ -288,6 +360,7 @@ where
 
     pub fn resize(&mut self, n: usize) {
         self.buffer.resize_with(n, Default::default);
+        self.write_pos %= n.max(1);
     }
 }
 
@@ -310,10 +383,49 @@ impl RingBuffer {
         let v2 = self.get_offset(delay, 1);
         let v3 = self.get_offset(delay, 2);
         let f = self.read_pos(delay) - self.read_pos(delay).trunc();
-        let a1 = 0.5 * (v2 - v0);
-        let a2 = v0 - 2.5 * v1 + 2.0 * v2 - 0.5 * v3;
-        let a3 = 0.5 * (v3 - v0) + 1.5 * (v1 - v2);
-        a3 * f * f * f + a2 * f * f + a1 * f + v1
+        hermite(v0, v1, v2, v3, f)
+    }
+
+    /// Lagrange cubic polynomial interpolation.
+    pub fn get_lagrange(&self, delay: f32) -> f32 {
+        let v0 = self.get_offset(delay, -1);
+        let v1 = self.get(delay);
+        let v2 = self.get_offset(delay, 1);
+        let v3 = self.get_offset(delay, 2);
+        let f = self.read_pos(delay) - self.read_pos(delay).trunc();
+        lagrange(v0, v1, v2, v3, f)
+    }
+
+    /// First order allpass interpolation. `state` is the previous output of
+    /// this read and must be kept between calls, one per read position. Best
+    /// suited to delays that change slowly, since a jump in `delay` rings.
+    pub fn get_allpass(&self, delay: f32, state: &mut f32) -> f32 {
+        let rp = self.read_pos(delay);
+        let d = 1.0 - (rp - rp.trunc());
+        let eta = (1.0 - d) / (1.0 + d);
+        let y = eta * (self.get_offset(delay, 1) - *state) + self.get(delay);
+        *state = y;
+        y
+    }
+
+    /// Read `delay` samples back using the buffer's `InterpolationMode`.
+    /// `Allpass` needs state, so it reads linearly here, see
+    /// `get_interpolated_with_state`.
+    pub fn get_interpolated(&self, delay: f32) -> f32 {
+        match self.interpolation {
+            InterpolationMode::Linear | InterpolationMode::Allpass => self.get_linear(delay),
+            InterpolationMode::Lagrange => self.get_lagrange(delay),
+            InterpolationMode::Hermite4 => self.get_cubic(delay),
+        }
+    }
+
+    /// Like `get_interpolated` but with the `state` of an `Allpass` read, one
+    /// per read position, kept by the caller between calls.
+    pub fn get_interpolated_with_state(&self, delay: f32, state: &mut f32) -> f32 {
+        match self.interpolation {
+            InterpolationMode::Allpass => self.get_allpass(delay, state),
+            _ => self.get_interpolated(delay),
+        }
     }
 }
 
@@ -325,6 +437,7 @@ where
         Self {
             buffer: Default::default(),
             write_pos: 0,
+            interpolation: InterpolationMode::default(),
         }
     }
 }
@@ -362,10 +475,50 @@ pub trait Signal {
     /// Responsible for updating any inputs including `phase` and returning the next signal
     /// output.
     fn signal(&self, rack: &mut Rack, sample_rate: f32);
+    /// Called by `Rack::reset` after the module's state has been restored and
+    /// its outputs and buffer cleared, for modules that keep state of their
+    /// own outside of the rack.
+    fn reset(&self, _rack: &mut Rack) {}
+    /// Called by `Rack::set_sample_rate` so modules can resize buffers sized
+    /// in samples. Controls, state and connections are left alone.
+    fn set_sample_rate(&self, _rack: &mut Rack, _sample_rate: f32) {}
+    /// Called by `Rack::play` in place of `signal` while the module is
+    /// bypassed. Processors pass their input straight through, the default
+    /// silences every output.
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs.outputs_mut(self.tag()).fill(0.0);
+    }
+    /// Called when the rack is cloned. Modules with state of their own
+    /// outside of the rack return a copy with that state duplicated, the
+    /// default of `None` shares the module between the clones.
+    fn duplicate(&self) -> Option<Arc<dyn Signal + Send + Sync>> {
+        None
+    }
+    /// The name of the module's type, used for introspection.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+    /// True if the module's outputs depend on nothing but its controls: it
+    /// keeps no state, draws no random numbers, ignores the sample rate and
+    /// reads no other module except through `V` and `S` controls, or `I`
+    /// controls holding the tag of an input. `Rack::optimize` evaluates a pure
+    /// module whose controls never change once, rather than every sample.
+    fn is_pure(&self) -> bool {
+        false
+    }
+    /// The names of the module's controls, in index order.
+    fn control_names(&self) -> &'static [&'static str] {
+        &[]
+    }
+    /// The names of the module's outputs, in index order.
+    fn output_names(&self) -> &'static [&'static str] {
+        &[]
+    }
 }
 
 /// A macro to reduce the boiler plate of creating a Synth Module by implementing
-/// `tag` and `modify_tag`.
+/// `tag` and `modify_tag`. Optionally pass the names of the module's controls
+/// in index order to implement `control_names`.
 #[macro_export]
 macro_rules! tag {
     () => {
@@ -376,26 +529,302 @@ macro_rules! tag {
             self.tag = f(self.tag);
         }
     };
+    ($($name:literal),+ $(,)?) => {
+        $crate::tag!();
+        fn control_names(&self) -> &'static [&'static str] {
+            &[$($name),+]
+        }
+    };
+}
+
+/// Description of a module in a `Rack`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ModuleInfo {
+    pub tag: Tag,
+    pub type_name: &'static str,
 }
 
+/// The output `from` of one module is patched into the control `to` of another,
+/// both given as (module, index) pairs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Connection {
+    pub from: (Tag, usize),
+    pub to: (Tag, usize),
+}
+
+/// How a `ParamRange` maps the normalized range [0, 1] onto its values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Skew {
+    Linear,
+    /// Equal steps are equal ratios, e.g. for frequencies. Both ends of the
+    /// range must be positive.
+    Log,
+}
+
+/// Limits, skew and smoothing for a control set with `Rack::set_param`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ParamRange {
+    pub min: f32,
+    pub max: f32,
+    pub skew: Skew,
+    /// Number of samples to ramp over when the value changes.
+    pub ramp: usize,
+}
+
+impl ParamRange {
+    pub fn new(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            skew: Skew::Linear,
+            ramp: 0,
+        }
+    }
+    pub fn log(mut self) -> Self {
+        self.skew = Skew::Log;
+        self
+    }
+    pub fn ramp(mut self, samples: usize) -> Self {
+        self.ramp = samples;
+        self
+    }
+    pub fn clamp(&self, value: f32) -> f32 {
+        value.clamp(self.min, self.max)
+    }
+    /// Map `x` in [0, 1] to a value in the range.
+    pub fn from_normalized(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self.skew {
+            Skew::Linear => self.min + (self.max - self.min) * x,
+            Skew::Log => self.min * (self.max / self.min).powf(x),
+        }
+    }
+    /// Map a value in the range to [0, 1].
+    pub fn to_normalized(&self, value: f32) -> f32 {
+        let value = self.clamp(value);
+        match self.skew {
+            Skew::Linear => (value - self.min) / (self.max - self.min),
+            Skew::Log => (value / self.min).ln() / (self.max / self.min).ln(),
+        }
+    }
+}
+
+/// A control moving linearly to a new value over a number of samples.
+#[derive(Debug, Copy, Clone)]
+struct Ramp {
+    to: (Tag, usize),
+    start: f32,
+    end: f32,
+    samples: usize,
+    elapsed: usize,
+    shape: RampShape,
+}
+
+/// The curve a ramped control follows, see `Rack::set_control_ramped_shaped`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RampShape {
+    /// Equal steps each sample.
+    #[default]
+    Linear,
+    /// Equal ratios each sample, slow at first then fast when rising, e.g. a
+    /// glide that is linear in pitch. Falls back to `Linear` unless both ends
+    /// are non-zero with the same sign.
+    Exponential,
+    /// The mirror image of `Exponential`, fast at first then slow when rising.
+    Logarithmic,
+}
+
+impl RampShape {
+    /// The value `t` (0 to 1) of the way from `start` to `end`.
+    fn interpolate(self, start: f32, end: f32, t: f32) -> f32 {
+        let geometric = start * end > 0.0;
+        match self {
+            RampShape::Exponential if geometric => start * (end / start).powf(t),
+            RampShape::Logarithmic if geometric => start + end - end * (start / end).powf(t),
+            _ => start + (end - start) * t,
+        }
+    }
+}
+
+/// A control change waiting for its sample, see `Rack::schedule_control`.
+#[derive(Debug, Copy, Clone)]
+struct ScheduledControl {
+    to: (Tag, usize),
+    value: Control,
+    frames: usize,
+}
+
+/// Errors reported by a `Rack`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RackError {
+    /// A module produced a NaN or infinite output.
+    NonFinite { tag: Tag },
+    /// There is no module with this tag in the rack.
+    ModuleNotFound { tag: Tag },
+    /// A control or output index is past the end of the module's storage.
+    IndexOutOfRange { tag: Tag, index: usize },
+    /// The control is already patched to that output.
+    DuplicateConnection {
+        from: (Tag, usize),
+        to: (Tag, usize),
+    },
+    /// The control is not patched to any output.
+    NotConnected { to: (Tag, usize) },
+    /// The connections form a cycle. `path` lists the modules around it,
+    /// starting and ending with the same module.
+    CycleDetected { path: Vec<Tag> },
+    /// A feedback connection whose source is processed before its
+    /// destination, so there is no delay.
+    NotFeedback {
+        from: (Tag, usize),
+        to: (Tag, usize),
+    },
+}
+
+impl std::fmt::Display for RackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RackError::NonFinite { tag } => {
+                write!(f, "module {} produced a non-finite output", tag.get())
+            }
+            RackError::ModuleNotFound { tag } => write!(f, "no module {}", tag.get()),
+            RackError::IndexOutOfRange { tag, index } => {
+                write!(f, "index {} out of range for module {}", index, tag.get())
+            }
+            RackError::DuplicateConnection { from, to } => write!(
+                f,
+                "control {}.{} is already connected to output {}.{}",
+                to.0.get(),
+                to.1,
+                from.0.get(),
+                from.1
+            ),
+            RackError::NotConnected { to } => {
+                write!(f, "control {}.{} is not connected", to.0.get(), to.1)
+            }
+            RackError::CycleDetected { path } => {
+                write!(f, "the connections form a cycle: ")?;
+                for (i, tag) in path.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " -> ")?;
+                    }
+                    write!(f, "{}", tag.get())?;
+                }
+                Ok(())
+            }
+            RackError::NotFeedback { from, to } => write!(
+                f,
+                "output {}.{} is processed before control {}.{}, so it cannot be feedback",
+                from.0.get(),
+                from.1,
+                to.0.get(),
+                to.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RackError {}
+
 /// A Rack is a topologically sorted `Array` of Synth Modules.  Along with the
 /// storage needed for each module: `Controls`, `State`, `Outputs`, and `Buffers`.
+///
+/// Cloning a rack copies all of that storage, so the clone has the same
+/// modules under the same tags, patched the same way, and then plays
+/// independently, e.g. to build one channel of a stereo effect and clone it for
+/// the other. Modules that keep all their state in the rack are shared between
+/// the clones, those with state of their own, such as a `SubRack` or an
+/// `Oscilloscope`, are copied with `Signal::duplicate`.
 pub struct Rack {
-    modules: Vec<Arc<dyn Signal + Send + Sync>>,
+    modules: Vec<Option<Arc<dyn Signal + Send + Sync>>>,
+    order: Vec<Tag>,
+    bypassed: Vec<bool>,
     pub controls: Box<Controls>,
     pub state: Box<State>,
     pub outputs: Box<Outputs>,
     pub buffers: Box<Buffers>,
+    nan_checks: bool,
+    non_finite: Option<Tag>,
+    ramps: Vec<Ramp>,
+    scheduled: Vec<ScheduledControl>,
+    feedback: Vec<Connection>,
+    ranges: HashMap<(Tag, usize), ParamRange>,
+    initial_state: Vec<[f32; MAX_STATE]>,
+    seed: Option<u64>,
+    folded: Vec<Tag>,
+    names: HashMap<String, Tag>,
+    sample_rate: Option<f32>,
+    start_fade: f32,
+    faded: f32,
+    fade_gain: f32,
+    #[cfg(feature = "profiling")]
+    profile: Vec<std::time::Duration>,
+}
+
+impl Clone for Rack {
+    fn clone(&self) -> Self {
+        let modules = self
+            .modules
+            .iter()
+            .map(|m| {
+                m.as_ref()
+                    .map(|m| m.duplicate().unwrap_or_else(|| m.clone()))
+            })
+            .collect();
+        Rack {
+            modules,
+            order: self.order.clone(),
+            bypassed: self.bypassed.clone(),
+            controls: self.controls.clone(),
+            state: self.state.clone(),
+            outputs: self.outputs.clone(),
+            buffers: self.buffers.clone(),
+            nan_checks: self.nan_checks,
+            non_finite: self.non_finite,
+            ramps: self.ramps.clone(),
+            scheduled: self.scheduled.clone(),
+            feedback: self.feedback.clone(),
+            ranges: self.ranges.clone(),
+            initial_state: self.initial_state.clone(),
+            seed: self.seed,
+            folded: self.folded.clone(),
+            names: self.names.clone(),
+            sample_rate: self.sample_rate,
+            start_fade: self.start_fade,
+            faded: self.faded,
+            fade_gain: self.fade_gain,
+            #[cfg(feature = "profiling")]
+            profile: self.profile.clone(),
+        }
+    }
 }
 
 impl Default for Rack {
     fn default() -> Self {
         Rack {
             modules: Vec::with_capacity(MAX_MODULES),
+            order: Vec::with_capacity(MAX_MODULES),
+            bypassed: Vec::with_capacity(MAX_MODULES),
             controls: Default::default(),
             state: Default::default(),
             outputs: Default::default(),
             buffers: Default::default(),
+            nan_checks: false,
+            non_finite: None,
+            ramps: Vec::with_capacity(MAX_CONTROLS),
+            scheduled: Vec::with_capacity(MAX_CONTROLS),
+            feedback: vec![],
+            ranges: HashMap::new(),
+            initial_state: Vec::with_capacity(MAX_MODULES),
+            seed: None,
+            folded: vec![],
+            names: HashMap::new(),
+            sample_rate: None,
+            start_fade: 0.0,
+            faded: 0.0,
+            fade_gain: 1.0,
+            #[cfg(feature = "profiling")]
+            profile: Vec::with_capacity(MAX_MODULES),
         }
     }
 }
@@ -404,21 +833,723 @@ impl Rack {
     pub fn new() -> Self {
         Self::default()
     }
+    /// When on, every output is checked after each module runs and NaN or
+    /// infinite values are replaced by 0. The first offending module is
+    /// reported by `try_play`. Off by default.
+    pub fn with_nan_checks(mut self, on: bool) -> Self {
+        self.nan_checks = on;
+        self
+    }
+    /// Seed the modules with randomness in them, e.g. noise, so that the rack
+    /// renders the same samples every time it is built. Each module gets its
+    /// own stream derived from `seed` and its tag, unless its builder was
+    /// given a seed. Without a seed they are seeded at random.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// Fade the outputs returned by `play`, the render methods and
+    /// `stereo_output` in from silence over `seconds` when the rack starts and
+    /// after each `reset`, hiding the click of filters and feedback loops
+    /// settling. The outputs modules read from each other are not faded.
+    pub fn with_start_fade(mut self, seconds: f32) -> Self {
+        self.start_fade = seconds.max(0.0);
+        self
+    }
+    /// The gain of the start fade for the sample just played, 1 once the fade
+    /// is over or without one.
+    pub fn fade_gain(&self) -> f32 {
+        self.fade_gain
+    }
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+    /// The seed for the random number generator of module `n`.
+    pub fn module_seed(&self, n: usize) -> u64 {
+        match self.seed {
+            Some(seed) => seed ^ (n as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            None => rand::random(),
+        }
+    }
     pub fn num_modules(&self) -> usize {
         self.modules.len()
     }
+    /// Add a module to the end of the rack. Its state at this point, e.g. an
+    /// initial phase set by a builder, is what `reset` restores.
     pub fn push(&mut self, module: Arc<dyn Signal + Send + Sync>) {
-        self.modules.push(module);
+        self.initial_state.push(self.state.0[self.modules.len()]);
+        self.order.push(self.modules.len().into());
+        self.bypassed.push(false);
+        #[cfg(feature = "profiling")]
+        self.profile.push(std::time::Duration::ZERO);
+        self.modules.push(Some(module));
+    }
+    /// Is there a module with this tag in the rack.
+    pub fn contains(&self, tag: Tag) -> bool {
+        matches!(self.modules.get(tag.get()), Some(Some(_)))
+    }
+    /// The output `index` of the module `tag`, or `None` if there is no such
+    /// module.
+    pub fn output(&self, tag: Tag, index: usize) -> Option<f32> {
+        if self.contains(tag) {
+            self.outputs.outputs(tag).get(index).copied()
+        } else {
+            None
+        }
+    }
+    /// Take a module out of the processing order without removing it, for a
+    /// module that calls its `signal` itself, e.g. `Oversample`.
+    pub(crate) fn detach(&mut self, tag: Tag) {
+        self.order.retain(|t| *t != tag);
+    }
+    /// Remove a module from the rack. Its controls, state, outputs and buffer
+    /// are cleared and any control patched to one of its outputs is set to
+    /// `0.0`. Tags of the remaining modules are not affected. Returns `false`
+    /// if there was no such module.
+    pub fn remove(&mut self, tag: Tag) -> bool {
+        if !self.contains(tag) {
+            return false;
+        }
+        self.modules[tag.get()] = None;
+        self.order.retain(|t| *t != tag);
+        self.folded.retain(|t| *t != tag);
+        self.names.retain(|_, t| *t != tag);
+        self.bypassed[tag.get()] = false;
+        #[cfg(feature = "profiling")]
+        {
+            self.profile[tag.get()] = std::time::Duration::ZERO;
+        }
+        self.feedback.retain(|c| c.from.0 != tag && c.to.0 != tag);
+        self.scheduled.retain(|e| e.to.0 != tag);
+        self.ranges.retain(|to, _| to.0 != tag);
+        self.controls.controls_mut(tag).fill(0.0.into());
+        self.state.state_mut(tag).fill(0.0);
+        self.initial_state[tag.get()] = [0.0; MAX_STATE];
+        self.outputs.outputs_mut(tag).fill(0.0);
+        self.buffers.set_buffer(tag, RingBuffer::default());
+        for cs in self.controls.0.iter_mut() {
+            for c in cs.iter_mut() {
+                if let Some((t, _)) = c.source() {
+                    if t == tag {
+                        *c = 0.0.into();
+                    }
+                }
+            }
+        }
+        true
+    }
+    /// Bypass a module, e.g. to compare a patch with and without it, without
+    /// removing it. A bypassed filter or effect passes its input through
+    /// unchanged and other modules output silence, see `Signal::bypass`.
+    pub fn set_bypassed(&mut self, tag: Tag, bypassed: bool) {
+        if self.contains(tag) {
+            self.bypassed[tag.get()] = bypassed;
+        }
+    }
+    pub fn is_bypassed(&self, tag: Tag) -> bool {
+        self.bypassed.get(tag.get()).copied().unwrap_or(false)
+    }
+    /// The tags of the modules in the order `play` calls them. Only `push`,
+    /// `remove`, `optimize` and `unfold` change it.
+    pub fn processing_order(&self) -> &[Tag] {
+        &self.order
+    }
+    /// Give module `tag` a name to find it by with `tag_by_name`, e.g. for a
+    /// patch file or host automation that should not depend on the order the
+    /// modules were built in. A module has at most one name and a name
+    /// belongs to one module, so either is taken from where it was before.
+    pub fn set_name(&mut self, tag: Tag, name: &str) -> Result<(), RackError> {
+        if !self.contains(tag) {
+            return Err(RackError::ModuleNotFound { tag });
+        }
+        self.names.retain(|_, t| *t != tag);
+        self.names.insert(name.to_string(), tag);
+        Ok(())
+    }
+    pub fn name(&self, tag: Tag) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|(_, t)| **t == tag)
+            .map(|(name, _)| name.as_str())
+    }
+    pub fn tag_by_name(&self, name: &str) -> Option<Tag> {
+        self.names.get(name).copied()
+    }
+    /// The modules in the rack in tag order, including those `play` does not
+    /// call directly, such as the modules inside an `Oversample`. See
+    /// `processing_order` for the order they are played in.
+    pub fn modules(&self) -> impl Iterator<Item = ModuleInfo> + '_ {
+        self.modules.iter().flatten().map(|m| ModuleInfo {
+            tag: m.tag(),
+            type_name: m.type_name(),
+        })
+    }
+    /// Every control that is patched to the output of a module, i.e. set to a
+    /// `Control::V` or `Control::S`. Inputs that a module reads directly by
+    /// `Tag` (such as the `wave` of a filter) are not included.
+    pub fn connections(&self) -> impl Iterator<Item = Connection> + '_ {
+        self.modules.iter().flatten().flat_map(move |m| {
+            let to = m.tag();
+            self.controls
+                .controls(to)
+                .iter()
+                .enumerate()
+                .filter_map(move |(i, c)| c.source().map(|from| Connection { from, to: (to, i) }))
+        })
+    }
+    /// Is the output `from` patched into the control `to`.
+    pub fn is_connected(&self, from: (Tag, usize), to: (Tag, usize)) -> bool {
+        self.contains(to.0) && to.1 < MAX_CONTROLS && self.controls[to].source() == Some(from)
+    }
+    /// Every control the output `from` is patched into.
+    pub fn connections_from(&self, from: (Tag, usize)) -> Vec<(Tag, usize)> {
+        self.connections()
+            .filter(|c| c.from == from)
+            .map(|c| c.to)
+            .collect()
+    }
+    /// Set several controls at once.
+    pub fn set_controls(&mut self, values: &[((Tag, usize), Control)]) {
+        for (to, value) in values {
+            self.ramps.retain(|r| r.to != *to);
+            self.controls[*to] = *value;
+        }
+    }
+    /// Move control `to` from its current value to `value` in a straight line
+    /// over `samples` calls to `play`, e.g. to avoid zipper noise.
+    pub fn set_control_ramped(&mut self, to: (Tag, usize), value: f32, samples: usize) {
+        self.set_control_ramped_shaped(to, value, samples, RampShape::Linear);
+    }
+    /// Like `set_control_ramped` along the curve `shape`.
+    pub fn set_control_ramped_shaped(
+        &mut self,
+        to: (Tag, usize),
+        value: f32,
+        samples: usize,
+        shape: RampShape,
+    ) {
+        self.ramps.retain(|r| r.to != to);
+        if samples == 0 {
+            self.controls[to] = value.into();
+            return;
+        }
+        let start = self.outputs.value(self.controls[to]).unwrap_or(0.0);
+        self.ramps.push(Ramp {
+            to,
+            start,
+            end: value,
+            samples,
+            elapsed: 0,
+            shape,
+        });
+    }
+    /// Ramp several controls at once, see `set_control_ramped`.
+    pub fn set_controls_ramped(&mut self, values: &[((Tag, usize), f32)], samples: usize) {
+        for (to, value) in values {
+            self.set_control_ramped(*to, *value, samples);
+        }
+    }
+    /// Set control `to` to `value` just before the `frame_offset`th call to
+    /// `play` from now, 0 being the next one. Events that arrive once per audio
+    /// block, e.g. timestamped midi, then land on their exact sample. Events
+    /// due on the same sample are applied in the order they were scheduled.
+    pub fn schedule_control(&mut self, to: (Tag, usize), value: Control, frame_offset: usize) {
+        self.scheduled.push(ScheduledControl {
+            to,
+            value,
+            frames: frame_offset,
+        });
+    }
+    fn apply_scheduled(&mut self) {
+        let mut i = 0;
+        while i < self.scheduled.len() {
+            if self.scheduled[i].frames == 0 {
+                let e = self.scheduled.remove(i);
+                self.ramps.retain(|r| r.to != e.to);
+                self.controls[e.to] = e.value;
+            } else {
+                self.scheduled[i].frames -= 1;
+                i += 1;
+            }
+        }
+    }
+    /// Give control `to` a range used by `set_param`.
+    pub fn set_range(&mut self, to: (Tag, usize), range: ParamRange) {
+        self.ranges.insert(to, range);
+    }
+    pub fn range(&self, to: (Tag, usize)) -> Option<ParamRange> {
+        self.ranges.get(&to).copied()
+    }
+    /// Set control `to` as a parameter: if it has a range the value is
+    /// clamped to it and ramped over the range's `ramp` samples, exponentially
+    /// for a `Log` range, otherwise it is set directly.
+    pub fn set_param(&mut self, to: (Tag, usize), value: f32) {
+        match self.range(to) {
+            Some(range) => {
+                let shape = match range.skew {
+                    Skew::Linear => RampShape::Linear,
+                    Skew::Log => RampShape::Exponential,
+                };
+                self.set_control_ramped_shaped(to, range.clamp(value), range.ramp, shape)
+            }
+            None => self.set_controls(&[(to, value.into())]),
+        }
+    }
+    /// Like `set_param` with `x` in [0, 1] mapped through the control's range,
+    /// e.g. from a knob or a host automation lane.
+    pub fn set_param_normalized(&mut self, to: (Tag, usize), x: f32) {
+        let value = match self.range(to) {
+            Some(range) => range.from_normalized(x),
+            None => x,
+        };
+        self.set_param(to, value);
+    }
+    fn advance_ramps(&mut self) {
+        let controls = &mut self.controls;
+        self.ramps.retain_mut(|r| {
+            r.elapsed += 1;
+            let t = r.elapsed as f32 / r.samples as f32;
+            controls[r.to] = r.shape.interpolate(r.start, r.end, t).into();
+            r.elapsed < r.samples
+        });
+    }
+    pub(crate) fn module(&self, tag: Tag) -> Option<&Arc<dyn Signal + Send + Sync>> {
+        self.modules.get(tag.get()).and_then(|m| m.as_ref())
+    }
+    /// Find a control of module `tag` by name, e.g. for host automation.
+    pub fn control_by_name(&self, tag: Tag, name: &str) -> Option<(Tag, usize)> {
+        let names = self.module(tag)?.control_names();
+        names.iter().position(|n| *n == name).map(|i| (tag, i))
+    }
+    /// Find an output of module `tag` by name.
+    pub fn output_by_name(&self, tag: Tag, name: &str) -> Option<(Tag, usize)> {
+        let names = self.module(tag)?.output_names();
+        names.iter().position(|n| *n == name).map(|i| (tag, i))
+    }
+    fn check_endpoint(&self, endpoint: (Tag, usize), len: usize) -> Result<(), RackError> {
+        let (tag, index) = endpoint;
+        if !self.contains(tag) {
+            Err(RackError::ModuleNotFound { tag })
+        } else if index >= len {
+            Err(RackError::IndexOutOfRange { tag, index })
+        } else {
+            Ok(())
+        }
+    }
+    /// Patch the output `from` into the control `to`, both given as (module,
+    /// index) pairs.
+    pub fn connect(&mut self, from: (Tag, usize), to: (Tag, usize)) -> Result<(), RackError> {
+        self.patch(from, to, Control::V(from.0, from.1))
+    }
+    /// Patch the output `from` into every control in `to`, e.g. the cutoff of
+    /// a group of voices for a keyboard split. Nothing is patched if any of
+    /// the connections would fail.
+    pub fn connect_many<I>(&mut self, from: (Tag, usize), to: I) -> Result<(), RackError>
+    where
+        I: IntoIterator<Item = (Tag, usize)>,
+    {
+        let to: Vec<(Tag, usize)> = to.into_iter().collect();
+        self.check_endpoint(from, MAX_OUTPUTS)?;
+        for t in &to {
+            self.check_endpoint(*t, MAX_CONTROLS)?;
+            if self.is_connected(from, *t) {
+                return Err(RackError::DuplicateConnection { from, to: *t });
+            }
+        }
+        for t in to {
+            self.controls[t] = Control::V(from.0, from.1);
+        }
+        Ok(())
+    }
+    /// Like `connect` but the output is multiplied by `scale`.
+    pub fn connect_scaled(
+        &mut self,
+        from: (Tag, usize),
+        to: (Tag, usize),
+        scale: f32,
+    ) -> Result<(), RackError> {
+        self.patch(from, to, Control::S(from.0, from.1, scale))
+    }
+    /// Patch the output `from` into the control `to` as a feedback loop. The
+    /// source must be processed at or after the destination, so that the
+    /// destination reads the previous sample of the source: a unit delay.
+    /// Cycles are only allowed by `validate` if they pass through a
+    /// feedback connection.
+    pub fn connect_feedback(
+        &mut self,
+        from: (Tag, usize),
+        to: (Tag, usize),
+    ) -> Result<(), RackError> {
+        if from.0.get() < to.0.get() {
+            return Err(RackError::NotFeedback { from, to });
+        }
+        self.connect(from, to)?;
+        self.feedback.push(Connection { from, to });
+        Ok(())
+    }
+    fn patch(
+        &mut self,
+        from: (Tag, usize),
+        to: (Tag, usize),
+        control: Control,
+    ) -> Result<(), RackError> {
+        self.check_endpoint(from, MAX_OUTPUTS)?;
+        self.check_endpoint(to, MAX_CONTROLS)?;
+        let duplicate = match (self.controls[to], control) {
+            (Control::V(t, i), Control::V(_, _)) => (t, i) == from,
+            (Control::S(t, i, a), Control::S(_, _, b)) => (t, i) == from && a == b,
+            _ => false,
+        };
+        if duplicate {
+            return Err(RackError::DuplicateConnection { from, to });
+        }
+        self.controls[to] = control;
+        Ok(())
+    }
+    /// Unpatch the control `to`, setting it to `0.0`.
+    pub fn disconnect(&mut self, to: (Tag, usize)) -> Result<(), RackError> {
+        self.check_endpoint(to, MAX_CONTROLS)?;
+        match self.controls[to].source() {
+            Some(_) => {
+                self.controls[to] = 0.0.into();
+                self.feedback.retain(|c| c.to != to);
+                Ok(())
+            }
+            None => Err(RackError::NotConnected { to }),
+        }
+    }
+    /// Unpatch every control connected to the output `from`, like pulling all
+    /// the cables out of a jack. Returns the number of connections removed.
+    pub fn disconnect_all_from(&mut self, from: (Tag, usize)) -> usize {
+        let targets = self.connections_from(from);
+        for to in &targets {
+            let _ = self.disconnect(*to);
+        }
+        targets.len()
+    }
+    /// Unpatch every control of the module `to`. Returns the number of
+    /// connections removed.
+    pub fn disconnect_all_into(&mut self, to: Tag) -> usize {
+        let targets: Vec<(Tag, usize)> = self
+            .connections()
+            .filter(|c| c.to.0 == to)
+            .map(|c| c.to)
+            .collect();
+        for to in &targets {
+            let _ = self.disconnect(*to);
+        }
+        targets.len()
+    }
+    /// Check that every connection comes from an existing module and that the
+    /// connections, other than those made with `connect_feedback`, do not form
+    /// a cycle.
+    pub fn validate(&self) -> Result<(), RackError> {
+        let mut edges = vec![vec![]; self.modules.len()];
+        for c in self.connections() {
+            self.check_endpoint(c.from, MAX_OUTPUTS)?;
+            if !self.feedback.contains(&c) {
+                edges[c.from.0.get()].push(c.to.0.get());
+            }
+        }
+        // Depth first search, a node that is reached again while it is still
+        // on the stack closes a cycle.
+        #[derive(Copy, Clone, PartialEq)]
+        enum Mark {
+            New,
+            Active,
+            Done,
+        }
+        fn visit(
+            node: usize,
+            edges: &[Vec<usize>],
+            marks: &mut [Mark],
+            stack: &mut Vec<usize>,
+        ) -> Option<Vec<usize>> {
+            marks[node] = Mark::Active;
+            stack.push(node);
+            for &next in &edges[node] {
+                match marks[next] {
+                    Mark::Active => {
+                        let start = stack.iter().position(|n| *n == next).unwrap();
+                        let mut path = stack[start..].to_vec();
+                        path.push(next);
+                        return Some(path);
+                    }
+                    Mark::New => {
+                        if let Some(path) = visit(next, edges, marks, stack) {
+                            return Some(path);
+                        }
+                    }
+                    Mark::Done => {}
+                }
+            }
+            stack.pop();
+            marks[node] = Mark::Done;
+            None
+        }
+        let mut marks = vec![Mark::New; self.modules.len()];
+        let mut stack = vec![];
+        for node in 0..self.modules.len() {
+            if marks[node] == Mark::New {
+                if let Some(path) = visit(node, &edges, &mut marks, &mut stack) {
+                    let path = path.into_iter().map(Tag::from).collect();
+                    return Err(RackError::CycleDetected { path });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Clear the outputs and buffers of every module and restore the state it
+    /// was pushed with, e.g. filter memory, oscillator phase and delay lines,
+    /// so the rack sounds as if it had just been built. Controls are left
+    /// untouched.
+    pub fn reset(&mut self) {
+        self.state.0.iter_mut().for_each(|s| s.fill(0.0));
+        self.state.0[..self.initial_state.len()].copy_from_slice(&self.initial_state);
+        self.outputs.0.iter_mut().for_each(|o| o.fill(0.0));
+        self.buffers.0.iter_mut().for_each(|b| b.clear());
+        self.faded = 0.0;
+        for i in 0..self.order.len() {
+            if let Some(module) = self.modules[self.order[i].get()].clone() {
+                module.reset(self);
+            }
+        }
+        self.evaluate_folded();
+    }
+    /// Take every module whose outputs can never change out of the processing
+    /// order, after evaluating it once. These are the modules
+    /// that are `Signal::is_pure` and whose controls are fixed values or
+    /// patched only to other such modules, e.g. constant offsets and the
+    /// arithmetic between them. Returns the tags of the folded modules.
+    ///
+    /// Modules are visited in processing order, so a module patched to one
+    /// that comes after it is not folded. Setting a control of a folded module
+    /// has no effect until `unfold` is called.
+    pub fn optimize(&mut self) -> Vec<Tag> {
+        let mut folded = vec![];
+        for i in 0..self.order.len() {
+            let tag = self.order[i];
+            if self.is_foldable(tag) {
+                self.folded.push(tag);
+                folded.push(tag);
+            }
+        }
+        self.order.retain(|t| !folded.contains(t));
+        self.evaluate_folded();
+        folded
+    }
+    /// Put the modules taken out by `optimize` back into the processing order.
+    pub fn unfold(&mut self) {
+        self.order.append(&mut self.folded);
+        self.order.sort_by_key(|t| t.get());
+    }
+    fn is_foldable(&self, tag: Tag) -> bool {
+        let Some(module) = &self.modules[tag.get()] else {
+            return false;
+        };
+        if !module.is_pure()
+            || self.bypassed[tag.get()]
+            || self.ramps.iter().any(|r| r.to.0 == tag)
+            || self.scheduled.iter().any(|e| e.to.0 == tag)
+        {
+            return false;
+        }
+        self.controls.controls(tag).iter().all(|c| match c {
+            Control::V(t, _) | Control::S(t, _, _) => self.folded.contains(t),
+            Control::I(t) => self.folded.contains(&Tag(*t)),
+            Control::F(_) | Control::B(_) => true,
+        })
+    }
+    fn evaluate_folded(&mut self) {
+        for i in 0..self.folded.len() {
+            if let Some(module) = self.modules[self.folded[i].get()].clone() {
+                // Pure modules ignore the sample rate, any will do.
+                module.signal(self, 44_100.0);
+            }
+        }
+    }
+    /// The sample rate the rack was last played at or told about with
+    /// `set_sample_rate`, e.g. for a control surface converting times to
+    /// samples. Modules are given it as an argument of `signal` instead.
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.sample_rate
+    }
+    /// Tell every module the rack will now be played at `sample_rate`, e.g.
+    /// after the host changes it, without rebuilding the rack.
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = Some(sample_rate);
+        for i in 0..self.order.len() {
+            if let Some(module) = self.modules[self.order[i].get()].clone() {
+                module.set_sample_rate(self, sample_rate);
+            }
+        }
     }
     /// Call the `signal` function for each module in turn returning the vector
     /// of outpts in the last module.
+    ///
+    /// Does not allocate, so it is safe to call from the audio thread. Modules
+    /// are visited in the cached `processing_order` and each module's `Arc` is
+    /// cloned in turn rather than the module list itself.
     pub fn play(&mut self, sample_rate: f32) -> [f32; MAX_OUTPUTS] {
         let n = self.modules.len() - 1;
-        let modules = self.modules.clone();
-        for module in modules.iter() {
-            module.signal(self, sample_rate);
+        self.sample_rate = Some(sample_rate);
+        if !self.scheduled.is_empty() {
+            self.apply_scheduled();
+        }
+        if !self.ramps.is_empty() {
+            self.advance_ramps();
+        }
+        for i in 0..self.order.len() {
+            let tag = self.order[i];
+            let Some(module) = self.modules[tag.get()].clone() else {
+                continue;
+            };
+            #[cfg(feature = "profiling")]
+            let start = std::time::Instant::now();
+            if self.bypassed[tag.get()] {
+                module.bypass(self);
+            } else {
+                module.signal(self, sample_rate);
+            }
+            #[cfg(feature = "profiling")]
+            {
+                self.profile[tag.get()] += start.elapsed();
+            }
+            if self.nan_checks {
+                self.check_finite(module.tag());
+            }
+        }
+        self.fade_gain = 1.0;
+        if self.faded < self.start_fade {
+            self.fade_gain = self.faded / self.start_fade;
+            self.faded += 1.0 / sample_rate;
+        }
+        self.outputs.0[n].map(|x| self.fade_gain * x)
+    }
+    /// Time spent processing each module since it was added or since
+    /// `clear_profile`, in processing order. Sort by the duration to find the
+    /// expensive modules of a patch.
+    #[cfg(feature = "profiling")]
+    pub fn profile(&self) -> Vec<(Tag, std::time::Duration)> {
+        self.order
+            .iter()
+            .map(|tag| (*tag, self.profile[tag.get()]))
+            .collect()
+    }
+    #[cfg(feature = "profiling")]
+    pub fn clear_profile(&mut self) {
+        self.profile.fill(std::time::Duration::ZERO);
+    }
+    fn check_finite(&mut self, tag: Tag) {
+        for x in self.outputs.outputs_mut(tag).iter_mut() {
+            if !x.is_finite() {
+                *x = 0.0;
+                self.non_finite.get_or_insert(tag);
+            }
+        }
+    }
+    /// Like `play` but returns an error naming the first module with a
+    /// non-finite output. Requires `with_nan_checks(true)`.
+    pub fn try_play(&mut self, sample_rate: f32) -> Result<[f32; MAX_OUTPUTS], RackError> {
+        let outputs = self.play(sample_rate);
+        match self.non_finite.take() {
+            Some(tag) => Err(RackError::NonFinite { tag }),
+            None => Ok(outputs),
+        }
+    }
+    /// Reset the rack and play it for `seconds`, collecting `output` (a module
+    /// and output index) at each sample.
+    pub fn render(&mut self, seconds: f32, sample_rate: f32, output: (Tag, usize)) -> Vec<f32> {
+        let n = (seconds * sample_rate) as usize;
+        let mut result = Vec::with_capacity(n);
+        self.reset();
+        for _ in 0..n {
+            self.play(sample_rate);
+            result.push(self.outputs[output] * self.fade_gain);
         }
-        self.outputs.0[n]
+        result
+    }
+    /// Like `render` but collecting two outputs.
+    pub fn render_stereo(
+        &mut self,
+        seconds: f32,
+        sample_rate: f32,
+        left: (Tag, usize),
+        right: (Tag, usize),
+    ) -> (Vec<f32>, Vec<f32>) {
+        let n = (seconds * sample_rate) as usize;
+        let mut result = (Vec::with_capacity(n), Vec::with_capacity(n));
+        self.reset();
+        for _ in 0..n {
+            self.play(sample_rate);
+            result.0.push(self.outputs[left] * self.fade_gain);
+            result.1.push(self.outputs[right] * self.fade_gain);
+        }
+        result
+    }
+    /// Render `seconds` of the given outputs, one per channel, to a wav file at
+    /// `path`. `bits_per_sample` must be 16 (integer) or 32 (float).
+    #[cfg(feature = "hound")]
+    pub fn render_to_wav(
+        &mut self,
+        seconds: f32,
+        sample_rate: f32,
+        path: &str,
+        channels: &[(Tag, usize)],
+        bits_per_sample: u16,
+    ) -> std::io::Result<()> {
+        use std::io::{Error, ErrorKind};
+        let sample_format = match bits_per_sample {
+            16 => hound::SampleFormat::Int,
+            32 => hound::SampleFormat::Float,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "bits_per_sample must be 16 or 32",
+                ))
+            }
+        };
+        let spec = hound::WavSpec {
+            channels: channels.len() as u16,
+            sample_rate: sample_rate as u32,
+            bits_per_sample,
+            sample_format,
+        };
+        let to_io = |e: hound::Error| match e {
+            hound::Error::IoError(e) => e,
+            e => Error::other(e),
+        };
+        let mut writer = hound::WavWriter::create(path, spec).map_err(to_io)?;
+        let n = (seconds * sample_rate) as usize;
+        self.reset();
+        for _ in 0..n {
+            self.play(sample_rate);
+            for &c in channels {
+                let x = self.outputs[c] * self.fade_gain;
+                if bits_per_sample == 16 {
+                    let x = (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                    writer.write_sample(x).map_err(to_io)?;
+                } else {
+                    writer.write_sample(x).map_err(to_io)?;
+                }
+            }
+        }
+        writer.finalize().map_err(to_io)
+    }
+    /// The left and right outputs of a stereo module, or `None` if there is no
+    /// such module. Scaled by the start fade.
+    pub fn stereo_output(&self, tag: Tag) -> Option<(f32, f32)> {
+        let gain = self.fade_gain;
+        Some((
+            gain * self.output(tag, LEFT)?,
+            gain * self.output(tag, RIGHT)?,
+        ))
+    }
+    /// Like play but only returns the left and right channels of the last
+    /// module, which should be a stereo module.
+    pub fn stereo(&mut self, sample_rate: f32) -> (f32, f32) {
+        let out = self.play(sample_rate);
+        (out[LEFT], out[RIGHT])
     }
     /// Like play but only returns the sample in `outputs[0].
     pub fn mono(&mut self, sample_rate: f32) -> f32 {
@@ -436,10 +1567,22 @@ macro_rules! build {
     };
 }
 
+/// Getter and setter for control `$n` of a module. The getter resolves the
+/// control each time it is called, i.e. every sample from `signal`, so a
+/// control patched to the output of another module, e.g. an LFO on a cutoff,
+/// is modulated at the audio rate.
 #[macro_export]
 macro_rules! props {
     ($field:ident, $set:ident, $n:expr) => {
         pub fn $field(&self, rack: &Rack) -> f32 {
+            const _: () = assert!(
+                $n < $crate::rack::MAX_CONTROLS,
+                concat!(
+                    "control `",
+                    stringify!($field),
+                    "` has an index past MAX_CONTROLS"
+                )
+            );
             let inp = rack.controls[(self.tag, $n)];
             rack.outputs.value(inp).unwrap()
         }
@@ -468,4 +1611,22 @@ mod tests {
         let result = rb.get_cubic(delay);
         assert_eq!(result, 3.75, "get_cubic returned {}, expected 3.75", result);
     }
+
+    #[test]
+    fn interpolation_modes() {
+        let mut rb = RingBuffer::new32(10.0);
+        for i in 0..=6 {
+            let x = i as f32;
+            rb.push(x * x * x);
+        }
+        let delay = 3.5;
+        assert_eq!(rb.interpolation(), InterpolationMode::Hermite4);
+        assert_eq!(rb.get_interpolated(delay), rb.get_cubic(delay));
+        let rb = rb.with_interpolation(InterpolationMode::Linear);
+        assert_eq!(rb.get_interpolated(delay), 17.5);
+        // Lagrange is exact for a cubic.
+        let mut rb = rb;
+        rb.set_interpolation(InterpolationMode::Lagrange);
+        assert_eq!(rb.get_interpolated(delay), 2.5 * 2.5 * 2.5);
+    }
 }
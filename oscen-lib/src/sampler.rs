@@ -0,0 +1,176 @@
+use crate::rack::*;
+use crate::utils::hermite;
+use crate::{build, props, tag};
+use std::sync::Arc;
+
+/// What a `SamplePlayer` does when it reaches the end of its sample.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    /// Play once and stop.
+    Off,
+    /// Jump back to the start position and keep playing.
+    Forward,
+}
+
+/// Plays a buffer of samples each time it is triggered. `speed` is the playback
+/// rate (2.0 is an octave up, negative plays backwards) and `start` is where
+/// playback begins as a fraction of the sample length. The sample is resampled
+/// from its source sample rate using cubic interpolation.
+#[derive(Clone)]
+pub struct SamplePlayer {
+    tag: Tag,
+    samples: Arc<Vec<f32>>,
+    source_rate: f32,
+    loop_mode: LoopMode,
+}
+
+impl SamplePlayer {
+    pub fn new<T: Into<Tag>>(
+        tag: T,
+        samples: Arc<Vec<f32>>,
+        source_rate: f32,
+        loop_mode: LoopMode,
+    ) -> Self {
+        Self {
+            tag: tag.into(),
+            samples,
+            source_rate,
+            loop_mode,
+        }
+    }
+
+    props!(speed, set_speed, 0);
+    props!(start, set_start, 1);
+
+    fn start_pos(&self, rack: &Rack) -> f32 {
+        let n = self.samples.len() as f32;
+        (self.start(rack).clamp(0.0, 1.0) * n).min(n - 1.0).max(0.0)
+    }
+
+    /// Start playing from the start position, restarting if already playing.
+    pub fn trigger(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 0)] = self.start_pos(rack);
+        rack.state[(self.tag, 1)] = 1.0;
+    }
+
+    pub fn stop(&self, rack: &mut Rack) {
+        rack.state[(self.tag, 1)] = 0.0;
+    }
+
+    pub fn playing(&self, rack: &Rack) -> bool {
+        rack.state[(self.tag, 1)] != 0.0
+    }
+
+    fn sample(&self, i: isize) -> f32 {
+        let n = self.samples.len() as isize;
+        let i = match self.loop_mode {
+            LoopMode::Off => i.clamp(0, n - 1),
+            LoopMode::Forward => i.rem_euclid(n),
+        };
+        self.samples[i as usize]
+    }
+}
+
+impl Signal for SamplePlayer {
+    tag!("speed", "start");
+
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        if !self.playing(rack) || self.samples.is_empty() {
+            rack.outputs[(self.tag, 0)] = 0.0;
+            return;
+        }
+        let n = self.samples.len() as f32;
+        let pos = rack.state[(self.tag, 0)];
+        let i = pos.floor() as isize;
+        rack.outputs[(self.tag, 0)] = hermite(
+            self.sample(i - 1),
+            self.sample(i),
+            self.sample(i + 1),
+            self.sample(i + 2),
+            pos - pos.floor(),
+        );
+        let mut pos = pos + self.speed(rack) * self.source_rate / sample_rate;
+        if pos >= n || pos < 0.0 {
+            match self.loop_mode {
+                LoopMode::Off => self.stop(rack),
+                LoopMode::Forward => {
+                    let start = self.start_pos(rack);
+                    let len = n - start;
+                    while pos >= n {
+                        pos -= len;
+                    }
+                    while pos < start {
+                        pos += len;
+                    }
+                }
+            }
+        }
+        rack.state[(self.tag, 0)] = pos;
+    }
+}
+
+#[derive(Clone)]
+pub struct SamplePlayerBuilder {
+    samples: Arc<Vec<f32>>,
+    source_rate: f32,
+    loop_mode: LoopMode,
+    speed: Control,
+    start: Control,
+}
+
+impl SamplePlayerBuilder {
+    pub fn from_samples(samples: Vec<f32>, source_rate: f32) -> Self {
+        Self {
+            samples: Arc::new(samples),
+            source_rate,
+            loop_mode: LoopMode::Off,
+            speed: 1.0.into(),
+            start: 0.0.into(),
+        }
+    }
+
+    /// Load a wav file, mixing multiple channels down to mono.
+    #[cfg(feature = "hound")]
+    pub fn from_wav(path: &str) -> Result<Self, hound::Error> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let frames: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 / scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let channels = spec.channels as usize;
+        let samples = frames
+            .chunks(channels)
+            .map(|c| c.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Ok(Self::from_samples(samples, spec.sample_rate as f32))
+    }
+
+    pub fn loop_mode(&mut self, value: LoopMode) -> &mut Self {
+        self.loop_mode = value;
+        self
+    }
+
+    build!(speed);
+    build!(start);
+
+    pub fn rack(&self, rack: &mut Rack) -> Arc<SamplePlayer> {
+        let n = rack.num_modules();
+        rack.controls[(n, 0)] = self.speed;
+        rack.controls[(n, 1)] = self.start;
+        let sp = Arc::new(SamplePlayer::new(
+            n,
+            self.samples.clone(),
+            self.source_rate,
+            self.loop_mode,
+        ));
+        rack.push(sp.clone());
+        sp
+    }
+}
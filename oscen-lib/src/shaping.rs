@@ -1,4 +1,6 @@
+use crate::filters::FilterType;
 use crate::rack::*;
+use crate::utils::zap_denormal;
 use crate::{props, tag};
 use std::f32::consts::PI;
 use std::sync::Arc;
@@ -19,7 +21,10 @@ impl SineFold {
 }
 
 impl Signal for SineFold {
-    tag!();
+    tag!("fold_param");
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
 
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         let fold_param = self.fold_param(rack);
@@ -61,6 +66,9 @@ impl Tanh {
 
 impl Signal for Tanh {
     tag!();
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
 
     fn signal(&self, rack: &mut Rack, _sample_rate: f32) {
         rack.outputs[(self.tag, 0)] = (rack.outputs[(self.wave, 0)] * 2.0 * PI).tanh();
@@ -84,3 +92,170 @@ impl TanhBuilder {
         t
     }
 }
+
+/// Q of the four sections of an 8th order Butterworth lowpass.
+const BUTTERWORTH_Q: [f32; 4] = [0.509_795_6, 0.601_344_9, 0.899_976_2, 2.562_915_5];
+
+/// Holds the oversampled input of an `Oversample` in its output for the inner
+/// modules to read. It is never played itself.
+#[derive(Debug, Copy, Clone)]
+struct OversampleInput {
+    tag: Tag,
+}
+
+impl Signal for OversampleInput {
+    tag!();
+    fn signal(&self, _rack: &mut Rack, _sample_rate: f32) {}
+}
+
+/// Runs the modules of a nonlinear patch, e.g. a `Tanh`, at `factor` times the
+/// sample rate so the harmonics they add above the Nyquist frequency are
+/// filtered out rather than aliased. The input is zero stuffed and lowpassed
+/// before each run of the inner modules, and their output is lowpassed again
+/// and every `factor`th sample kept. Both filters are 8th order Butterworth
+/// with a cutoff at 0.4 times the sample rate, steep enough that images of
+/// the input do not mix with each other in the nonlinearity back down to
+/// audible frequencies.
+///
+/// The inner modules read the oversampled input from the `input` tag, are
+/// taken out of the rack's processing order and are played, reset and told
+/// the sample rate by this module only. Modules they read other than the
+/// input hold their value for the `factor` runs.
+#[derive(Debug, Clone)]
+pub struct Oversample {
+    tag: Tag,
+    wave: Tag,
+    input: Tag,
+    inner: Vec<Tag>,
+    factor: usize,
+}
+
+impl Oversample {
+    pub fn new<T: Into<Tag>>(
+        tag: T,
+        wave: Tag,
+        input: Tag,
+        inner: Vec<Tag>,
+        factor: usize,
+    ) -> Self {
+        Self {
+            tag: tag.into(),
+            wave,
+            input,
+            inner,
+            factor: factor.max(1),
+        }
+    }
+
+    /// The tag the inner modules read the oversampled input from.
+    pub fn input(&self) -> Tag {
+        self.input
+    }
+
+    pub fn inner(&self) -> &[Tag] {
+        &self.inner
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Run `x` through the four biquads with state starting at `offset`.
+    fn lowpass(
+        &self,
+        rack: &mut Rack,
+        offset: usize,
+        coefficients: &[([f32; 3], [f32; 2]); 4],
+        x: f32,
+    ) -> f32 {
+        let tag = self.tag;
+        coefficients.iter().enumerate().fold(x, |x, (i, (b, a))| {
+            let s = offset + 2 * i;
+            let y = b[0] * x + rack.state[(tag, s)];
+            rack.state[(tag, s)] = zap_denormal(b[1] * x - a[0] * y + rack.state[(tag, s + 1)]);
+            rack.state[(tag, s + 1)] = zap_denormal(b[2] * x - a[1] * y);
+            y
+        })
+    }
+}
+
+impl Signal for Oversample {
+    tag!();
+    fn bypass(&self, rack: &mut Rack) {
+        rack.outputs[(self.tag, 0)] = rack.outputs[(self.wave, 0)];
+    }
+    fn reset(&self, rack: &mut Rack) {
+        for tag in &self.inner {
+            if let Some(module) = rack.module(*tag).cloned() {
+                module.reset(rack);
+            }
+        }
+    }
+    fn set_sample_rate(&self, rack: &mut Rack, sample_rate: f32) {
+        for tag in &self.inner {
+            if let Some(module) = rack.module(*tag).cloned() {
+                module.set_sample_rate(rack, sample_rate * self.factor as f32);
+            }
+        }
+    }
+    fn signal(&self, rack: &mut Rack, sample_rate: f32) {
+        let rate = sample_rate * self.factor as f32;
+        let coefficients = BUTTERWORTH_Q
+            .map(|q| FilterType::Lowpass.coefficients(0.4 * sample_rate, q, 0.0, rate));
+        let out_tag = *self.inner.last().unwrap_or(&self.input);
+        let x = rack.outputs[(self.wave, 0)];
+        let mut out = 0.0;
+        for i in 0..self.factor {
+            // Zero stuffing spreads the input over `factor` samples.
+            let up = if i == 0 { x * self.factor as f32 } else { 0.0 };
+            rack.outputs[(self.input, 0)] = self.lowpass(rack, 0, &coefficients, up);
+            for tag in &self.inner {
+                if let Some(module) = rack.module(*tag).cloned() {
+                    module.signal(rack, rate);
+                }
+            }
+            out = self.lowpass(rack, 8, &coefficients, rack.outputs[(out_tag, 0)]);
+        }
+        rack.outputs[(self.tag, 0)] = out;
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct OversampleBuilder {
+    wave: Tag,
+    factor: usize,
+}
+
+impl OversampleBuilder {
+    pub fn new(wave: Tag) -> Self {
+        Self { wave, factor: 2 }
+    }
+
+    pub fn factor(&mut self, value: usize) -> &mut Self {
+        self.factor = value;
+        self
+    }
+
+    /// Add the modules built by `inner`, which is given the tag to read the
+    /// oversampled input from, followed by the oversampler. The output of the
+    /// last module `inner` adds is the one that is downsampled.
+    pub fn rack<R>(
+        &self,
+        rack: &mut Rack,
+        inner: impl FnOnce(&mut Rack, Tag) -> R,
+    ) -> (Arc<Oversample>, R) {
+        let m = rack.num_modules();
+        let input: Tag = m.into();
+        rack.push(Arc::new(OversampleInput { tag: input }));
+        let result = inner(rack, input);
+        let n = rack.num_modules();
+        let tags: Vec<Tag> = (m + 1..n).map(Tag::from).collect();
+        rack.detach(input);
+        for tag in &tags {
+            rack.detach(*tag);
+        }
+        let os = Arc::new(Oversample::new(n, self.wave, input, tags, self.factor));
+        rack.push(os.clone());
+        (os, result)
+    }
+}
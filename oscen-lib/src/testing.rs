@@ -0,0 +1,105 @@
+use crate::oscillators::{Const, ConstBuilder};
+use crate::rack::*;
+use std::sync::Arc;
+
+/// Drives a single module, e.g. in a unit test, without setting up the rest
+/// of a rack by hand. The module reads its input from a `Const` set with
+/// `set_input` and its controls and outputs are found by name.
+pub struct Harness<M> {
+    pub rack: Rack,
+    input: Arc<Const>,
+    module: Arc<M>,
+    sample_rate: f32,
+}
+
+impl<M: Signal + Send + Sync + 'static> Harness<M> {
+    /// Build the module with `build`, which is given the tag to read the
+    /// input from. The sample rate defaults to 44.1 kHz.
+    pub fn new(build: impl FnOnce(&mut Rack, Tag) -> Arc<M>) -> Self {
+        let mut rack = Rack::default();
+        let input = ConstBuilder::new(0.0.into()).rack(&mut rack);
+        let module = build(&mut rack, input.tag());
+        Self {
+            rack,
+            input,
+            module,
+            sample_rate: 44_100.0,
+        }
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: f32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn module(&self) -> &Arc<M> {
+        &self.module
+    }
+
+    pub fn set_input(&mut self, value: f32) {
+        self.input.set_value(&mut self.rack, value.into());
+    }
+
+    /// Set the control called `name`. Panics if the module has no such
+    /// control.
+    pub fn set_control(&mut self, name: &str, value: f32) {
+        let to = self
+            .rack
+            .control_by_name(self.module.tag(), name)
+            .unwrap_or_else(|| panic!("no control named {name}"));
+        self.rack.controls[to] = value.into();
+    }
+
+    /// Play one sample and return output 0 of the module, scaled by the start
+    /// fade of the rack if it has one.
+    pub fn process(&mut self) -> f32 {
+        self.rack.play(self.sample_rate);
+        self.rack.outputs[(self.module.tag(), 0)] * self.rack.fade_gain()
+    }
+
+    /// Play `samples` samples, collecting output 0 of the module.
+    pub fn run(&mut self, samples: usize) -> Vec<f32> {
+        (0..samples).map(|_| self.process()).collect()
+    }
+
+    /// Play a sample for each value of `input`, collecting output 0.
+    pub fn run_input(&mut self, input: &[f32]) -> Vec<f32> {
+        input
+            .iter()
+            .map(|x| {
+                self.set_input(*x);
+                self.process()
+            })
+            .collect()
+    }
+
+    /// The output called `name`. Panics if the module has no such output.
+    pub fn output(&self, name: &str) -> f32 {
+        let from = self
+            .rack
+            .output_by_name(self.module.tag(), name)
+            .unwrap_or_else(|| panic!("no output named {name}"));
+        self.rack.outputs[from]
+    }
+}
+
+/// Amplitude of the `hz` component of `xs`, from a single bin of a discrete
+/// Fourier transform. Exact for a whole number of cycles of `hz` in `xs`.
+pub fn partial(xs: &[f32], hz: f32, sample_rate: f32) -> f32 {
+    let w = 2.0 * std::f64::consts::PI * hz as f64 / sample_rate as f64;
+    let (re, im) = xs.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, x)| {
+        let x = *x as f64;
+        (re + x * (w * i as f64).cos(), im - x * (w * i as f64).sin())
+    });
+    (2.0 * (re * re + im * im).sqrt() / xs.len() as f64) as f32
+}
+
+/// The largest magnitude in `xs`.
+pub fn peak(xs: &[f32]) -> f32 {
+    xs.iter().fold(0.0, |m, x| m.max(x.abs()))
+}
+
+/// The root mean square of `xs`, NaN if `xs` is empty.
+pub fn rms(xs: &[f32]) -> f32 {
+    (xs.iter().map(|x| x * x).sum::<f32>() / xs.len() as f32).sqrt()
+}
@@ -1,5 +1,7 @@
 use crate::rack::*;
 use approx::relative_eq;
+use rand::RngCore;
+use rand_distr::{Distribution, Uniform};
 
 /// Given f(0) = low, f(1/2) = mid, and f(1) = high, let f(x) = a + b*exp(cs).
 /// Fit a, b, and c so to match the above. If mid < 1/2(high + low) then f is
@@ -26,6 +28,84 @@ pub fn interp_inv(low: f32, mid: f32, high: f32, y: f32) -> f32 {
     }
 }
 
+/// A Hann window of length `n`.
+pub fn hann(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / n as f32).cos())
+        .collect()
+}
+
+/// A Hamming window of length `n`. Lower first sidelobe than a Hann window
+/// but it does not reach zero at the edges.
+pub fn hamming(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| 0.54 - 0.46 * (std::f32::consts::TAU * i as f32 / n as f32).cos())
+        .collect()
+}
+
+/// A Blackman window of length `n`. Wider main lobe than a Hann window and
+/// much lower sidelobes.
+pub fn blackman(n: usize) -> Vec<f32> {
+    (0..n)
+        .map(|i| {
+            let x = std::f32::consts::TAU * i as f32 / n as f32;
+            0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos()
+        })
+        .collect()
+}
+
+/// Number of state values `pink_filter` needs.
+pub const PINK_STATE: usize = 7;
+
+/// Paul Kellet's filter from white to pink (-3 dB per octave) noise. `state`
+/// holds `PINK_STATE` values kept between samples, starting at 0.
+pub fn pink_filter(state: &mut [f32], white: f32) -> f32 {
+    state[0] = 0.99886 * state[0] + white * 0.0555179;
+    state[1] = 0.99332 * state[1] + white * 0.0750759;
+    state[2] = 0.969 * state[2] + white * 0.153852;
+    state[3] = 0.8665 * state[3] + white * 0.3104856;
+    state[4] = 0.55 * state[4] + white * 0.5329522;
+    state[5] = -0.7616 * state[5] - white * 0.016898;
+    let pink = state[..PINK_STATE].iter().sum::<f32>() + white * 0.5362;
+    state[6] = white * 0.115926;
+    pink
+}
+
+/// Uniform white noise between -1 and 1, the same sequence for each `seed`.
+pub fn white_noise(seed: u64) -> impl Iterator<Item = f32> {
+    let mut rng = Xorshift32::new(seed);
+    let dist = Uniform::new_inclusive(-1.0, 1.0);
+    std::iter::repeat_with(move || dist.sample(&mut rng))
+}
+
+/// Flush very small values to zero, so that decaying feedback loops do not
+/// end up computing with (slow) denormal floats.
+pub fn zap_denormal(x: f32) -> f32 {
+    if x.abs() < 1e-15 {
+        0.0
+    } else {
+        x
+    }
+}
+
+/// Hermite cubic polynomial interpolation between `v1` and `v2`, where `v0` and
+/// `v3` are the neighbouring samples and `f` is the fractional position.
+pub fn hermite(v0: f32, v1: f32, v2: f32, v3: f32, f: f32) -> f32 {
+    let a1 = 0.5 * (v2 - v0);
+    let a2 = v0 - 2.5 * v1 + 2.0 * v2 - 0.5 * v3;
+    let a3 = 0.5 * (v3 - v0) + 1.5 * (v1 - v2);
+    a3 * f * f * f + a2 * f * f + a1 * f + v1
+}
+
+/// Lagrange cubic polynomial interpolation between `v1` and `v2`, where `v0`
+/// and `v3` are the neighbouring samples and `f` is the fractional position.
+pub fn lagrange(v0: f32, v1: f32, v2: f32, v3: f32, f: f32) -> f32 {
+    let a = f + 1.0;
+    let b = f - 1.0;
+    let c = f - 2.0;
+    -f * b * c / 6.0 * v0 + a * b * c / 2.0 * v1 - a * f * c / 2.0 * v2 + a * f * b / 6.0 * v3
+}
+
 pub fn signals(rack: &mut Rack, start: u32, end: u32, sample_rate: f32) -> Vec<(f32, f32)> {
     let mut result = vec![];
     for i in start..=end {
@@ -34,6 +114,69 @@ pub fn signals(rack: &mut Rack, start: u32, end: u32, sample_rate: f32) -> Vec<(
     result
 }
 
+/// Xorshift random number generator for the modules with randomness in them.
+/// Its whole state is one `u32`, which a module keeps in one of its `State`
+/// slots with `to_state` and reads back with `from_state`, so that `reset`
+/// replays the same sequence from the seed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Xorshift32(u32);
+
+impl Xorshift32 {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads nearby seeds, e.g. module numbers, apart.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self::from_bits(z as u32 ^ (z >> 32) as u32)
+    }
+
+    /// A zero state, e.g. from a module that was not seeded, would only ever
+    /// produce zeros so it is replaced by a fixed seed.
+    fn from_bits(bits: u32) -> Self {
+        if bits == 0 {
+            Self(0x6D2B_79F5)
+        } else {
+            Self(bits)
+        }
+    }
+
+    pub fn from_state(state: f32) -> Self {
+        Self::from_bits(state.to_bits())
+    }
+
+    pub fn to_state(self) -> f32 {
+        f32::from_bits(self.0)
+    }
+}
+
+impl RngCore for Xorshift32 {
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,6 +186,34 @@ mod tests {
         (10_000.0 * x + 0.5) as i32
     }
     #[test]
+    fn windows() {
+        let w = hann(4);
+        assert!(relative_eq!(w[0], 0.0) && relative_eq!(w[2], 1.0));
+        let w = hamming(4);
+        assert!(relative_eq!(w[0], 0.08) && relative_eq!(w[2], 1.0));
+        let w = blackman(4);
+        assert!(w[0].abs() < 1e-6 && relative_eq!(w[2], 1.0));
+    }
+    #[test]
+    fn noise() {
+        let a: Vec<f32> = white_noise(3).take(64).collect();
+        assert_eq!(a, white_noise(3).take(64).collect::<Vec<f32>>());
+        assert!(a.iter().all(|x| (-1.0..=1.0).contains(x)));
+        // Pink noise has most of its energy in the low frequencies, so it
+        // changes less from one sample to the next than white noise.
+        let mut state = [0.0; PINK_STATE];
+        let pink: Vec<f32> = white_noise(3)
+            .take(4096)
+            .map(|w| pink_filter(&mut state, w))
+            .collect();
+        let roughness = |xs: &[f32]| {
+            let diff: f32 = xs.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            diff / xs.iter().map(|x| x * x).sum::<f32>()
+        };
+        let white: Vec<f32> = white_noise(3).take(4096).collect();
+        assert!(roughness(&pink) < 0.5 * roughness(&white));
+    }
+    #[test]
     fn linear_interp() {
         fn ie(x: f32) -> f32 {
             interp(0.0, 0.5, 1.0, x)
@@ -0,0 +1,96 @@
+use oscen::dynamics::*;
+use oscen::envelopes::*;
+use oscen::filters::*;
+use oscen::midi::*;
+use oscen::operators::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|a| a.get())
+}
+
+#[test]
+fn play_does_not_allocate() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(sine_osc).hz(3.0).rack(&mut rack);
+    let osc = OscBuilder::new(saw_osc).hz(220.0).rack(&mut rack);
+    let adsr = AdsrBuilder::linear().rack(&mut rack);
+    let lpf = LpfBuilder::new(osc.tag())
+        .cut_off(lfo.tag() * 500.0)
+        .rack(&mut rack);
+    let delay = DelayBuilder::new(lpf.tag(), 0.01.into()).rack(&mut rack);
+    let mixer = MixerBuilder::new(vec![lpf.tag(), delay.tag()]).rack(&mut rack);
+    let meter = LevelMeterBuilder::new(mixer.tag()).rack(&mut rack);
+    VcaBuilder::new(mixer.tag())
+        .level(adsr.tag())
+        .rack(&mut rack);
+    rack.set_control_ramped((osc.tag(), 0), 440.0, 64);
+    adsr.on(&mut rack);
+    rack.play(44_100.0);
+
+    let before = allocations();
+    for _ in 0..1024 {
+        rack.play(44_100.0);
+    }
+    assert_eq!(allocations(), before);
+    assert!(rack.outputs[(meter.tag(), 0)] > 0.0);
+}
+
+#[test]
+fn first_control_change_does_not_allocate() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc).hz(220.0).rack(&mut rack);
+
+    let before = allocations();
+    rack.schedule_control((osc.tag(), 0), 440.0.into(), 4);
+    rack.set_control_ramped((osc.tag(), 1), 0.5, 64);
+    for _ in 0..8 {
+        rack.play(44_100.0);
+    }
+    assert_eq!(allocations(), before);
+    assert_eq!(osc.hz(&rack), 440.0);
+}
+
+#[test]
+fn voice_allocator_does_not_allocate() {
+    let mut va: VoiceAllocator<1> = VoiceAllocator::default();
+    let messages = [
+        [0xB0, SUSTAIN_PEDAL, 127],
+        [0x90, 60, 100],
+        [0x90, 64, 100],
+        [0xA0, 64, 90],
+        [0x80, 64, 0],
+        [0xB0, SUSTAIN_PEDAL, 0],
+    ]
+    .map(|bytes| MidiMessage::from_bytes(&bytes).unwrap());
+
+    let before = allocations();
+    let mut commands = 0;
+    for message in messages {
+        commands += va.message(message).count();
+    }
+    assert_eq!(allocations(), before);
+    assert_eq!(commands, 5);
+}
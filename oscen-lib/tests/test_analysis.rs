@@ -0,0 +1,141 @@
+#[cfg(feature = "fft")]
+#[test]
+fn spectrum_analyzer() {
+    use oscen::analysis::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let sine = OscBuilder::new(sine_osc).hz(8.0).rack(&mut rack);
+    let sa = SpectrumAnalyzerBuilder::new(sine.tag())
+        .size(64)
+        .hop(16)
+        .rack(&mut rack);
+    let handle = sa.handle();
+    assert_eq!(handle.magnitudes(), vec![0.0; 33]);
+    for _ in 0..64 {
+        rack.mono(64.0);
+    }
+    let mags = handle.magnitudes();
+    assert!((mags[8] - 1.0).abs() < 1e-3);
+    assert!(mags[12] < 1e-3);
+}
+
+#[test]
+fn oscilloscope_trigger() {
+    use oscen::analysis::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let saw = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let scope = OscilloscopeBuilder::new(saw.tag())
+        .length(4)
+        .trigger_mode(TriggerMode::Rising { level: 0.5 })
+        .holdoff(1.0)
+        .rack(&mut rack);
+    let handle = scope.handle();
+    for _ in 0..8 {
+        rack.mono(8.0);
+    }
+    assert_eq!(handle.frame(), vec![0.5, 0.625, 0.75, 0.875]);
+
+    handle.set_trigger_mode(TriggerMode::Falling { level: 0.5 });
+    assert_eq!(handle.trigger_mode(), TriggerMode::Falling { level: 0.5 });
+    // The holdoff skips the falling edge right after the first frame.
+    for _ in 0..16 {
+        rack.mono(8.0);
+    }
+    assert_eq!(handle.frame(), vec![0.0, 0.125, 0.25, 0.375]);
+}
+
+#[test]
+fn oscilloscope_reset() {
+    use oscen::analysis::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let scope = OscilloscopeBuilder::new(c.tag()).length(2).rack(&mut rack);
+    for _ in 0..4 {
+        rack.mono(1.0);
+    }
+    assert_eq!(scope.handle().frame(), vec![0.5, 0.5]);
+    rack.reset();
+    assert_eq!(scope.handle().frame(), vec![0.0, 0.0]);
+}
+
+#[test]
+fn oscilloscope_channels() {
+    use oscen::analysis::*;
+    use oscen::operators::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let saw = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let gain = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let louder = VcaBuilder::new(saw.tag()).level(gain.tag()).rack(&mut rack);
+    let scope = OscilloscopeBuilder::new(saw.tag())
+        .channels(&[louder.tag()])
+        .length(3)
+        .trigger_mode(TriggerMode::Rising { level: 0.5 })
+        .rack(&mut rack);
+    let handle = scope.handle();
+    assert_eq!(handle.channels(), 2);
+    for _ in 0..8 {
+        rack.mono(8.0);
+    }
+    assert_eq!(
+        handle.frames(),
+        vec![vec![0.5, 0.625, 0.75], vec![1.0, 1.25, 1.5]]
+    );
+    assert_eq!(handle.frame(), handle.frames()[0]);
+    assert_eq!(rack.outputs[(scope.tag(), 1)], 1.75);
+}
+
+#[test]
+fn oscilloscope_external_trigger() {
+    use oscen::analysis::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let saw = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let gate = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let scope = OscilloscopeBuilder::new(saw.tag())
+        .length(3)
+        .trigger_mode(TriggerMode::External {
+            source: gate.tag(),
+            level: 0.5,
+        })
+        .rack(&mut rack);
+    let handle = scope.handle();
+    for _ in 0..3 {
+        rack.mono(8.0);
+    }
+    // The input's own edges do not trigger, the gate opening does.
+    gate.set_value(&mut rack, 1.0.into());
+    for _ in 0..8 {
+        rack.mono(8.0);
+    }
+    assert_eq!(handle.frame(), vec![0.375, 0.5, 0.625]);
+}
+
+#[test]
+fn oscilloscope_empty_frame() {
+    use oscen::analysis::*;
+    use oscen::oscillators::*;
+    use oscen::rack::*;
+
+    let mut rack = Rack::default();
+    let saw = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let scope = OscilloscopeBuilder::new(saw.tag())
+        .length(0)
+        .rack(&mut rack);
+    for _ in 0..8 {
+        rack.mono(8.0);
+    }
+    assert_eq!(scope.handle().frame().len(), 1);
+}
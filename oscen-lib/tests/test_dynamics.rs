@@ -0,0 +1,149 @@
+use oscen::dynamics::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::testing::*;
+
+#[test]
+fn level_meter() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new((-0.5).into()).rack(&mut rack);
+    let meter = LevelMeterBuilder::new(c.tag())
+        .window(4)
+        .attack(0.0)
+        .release(1.0)
+        .rack(&mut rack);
+    rack.mono(1.0);
+    assert_eq!(meter.peak(&rack), 0.5);
+    assert_eq!(meter.rms(&rack), 0.25);
+    for _ in 0..3 {
+        rack.mono(1.0);
+    }
+    assert_eq!(meter.rms(&rack), 0.5);
+    c.set_value(&mut rack, 0.0.into());
+    rack.mono(1.0);
+    assert!((meter.peak(&rack) - 0.5 / std::f32::consts::E).abs() < 1e-6);
+    for _ in 0..3 {
+        rack.mono(1.0);
+    }
+    assert_eq!(meter.rms(&rack), 0.0);
+}
+
+#[test]
+fn level_meter_empty_window() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new((-0.5).into()).rack(&mut rack);
+    let meter = LevelMeterBuilder::new(c.tag())
+        .window(0)
+        .attack(0.0)
+        .rack(&mut rack);
+    rack.mono(1.0);
+    assert_eq!(meter.rms(&rack), 0.5);
+}
+
+#[test]
+fn level_meter_names() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let meter = LevelMeterBuilder::new(c.tag()).rack(&mut rack);
+    assert_eq!(
+        rack.output_by_name(meter.tag(), "rms"),
+        Some((meter.tag(), 1))
+    );
+    assert_eq!(
+        rack.control_by_name(meter.tag(), "release"),
+        Some((meter.tag(), 1))
+    );
+}
+
+#[test]
+fn noise_gate() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    NoiseGateBuilder::new(c.tag())
+        .threshold(0.1)
+        .attack(0.0)
+        .release(0.0)
+        .hold(2.0)
+        .rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.5);
+    c.set_value(&mut rack, 0.05.into());
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![0.05, 0.0, 0.0]);
+}
+
+#[test]
+fn noise_gate_no_hold() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    NoiseGateBuilder::new(c.tag())
+        .threshold(0.1)
+        .attack(0.0)
+        .release(0.0)
+        .hold(0.0)
+        .rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.5);
+    assert_eq!(rack.mono(1.0), 0.5);
+    c.set_value(&mut rack, 0.05.into());
+    assert_eq!(rack.mono(1.0), 0.0);
+}
+
+#[test]
+fn limiter() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.25.into()).rack(&mut rack);
+    let limiter = LimiterBuilder::new(c.tag())
+        .lookahead(3)
+        .threshold(0.5)
+        .rack(&mut rack);
+    let mut rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    c.set_value(&mut rack, 1.0.into());
+    rs.extend((0..6).map(|_| rack.mono(1.0)));
+    // The gain ramps from 1 to 0.5 over the lookahead, before the step
+    // reaches the output.
+    assert_eq!(
+        rs,
+        vec![0.0, 0.0, 0.0, 0.21875, 0.1875, 0.15625, 0.5, 0.5, 0.5]
+    );
+    assert_eq!(limiter.gain(&rack), 0.5);
+}
+
+#[test]
+fn limiter_ceiling() {
+    let mut rack = Rack::default();
+    let noise = WhiteNoiseBuilder::new().amplitude(2.0).rack(&mut rack);
+    LimiterBuilder::new(noise.tag())
+        .threshold(0.5)
+        .rack(&mut rack);
+    for _ in 0..10_000 {
+        assert!(rack.mono(44_100.0).abs() <= 0.5 + 1e-6);
+    }
+}
+
+#[test]
+fn limiter_clipped() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let limiter = LimiterBuilder::new(c.tag()).lookahead(4).rack(&mut rack);
+    for _ in 0..100 {
+        rack.mono(44_100.0);
+    }
+    assert!(!limiter.clipped(&rack));
+    limiter.set_threshold(&mut rack, 1.5.into());
+    for _ in 0..100 {
+        rack.mono(44_100.0);
+    }
+    assert!(limiter.clipped(&rack));
+    limiter.clear_clipped(&mut rack);
+    assert!(!limiter.clipped(&rack));
+}
+
+#[test]
+fn level_meter_harness() {
+    let mut h = Harness::new(|rack, input| LevelMeterBuilder::new(input).window(4).rack(rack))
+        .with_sample_rate(1.0);
+    h.set_control("attack", 0.0);
+    h.run_input(&[-0.5, 0.5, -0.5, 0.5]);
+    assert_eq!(h.output("peak"), 0.5);
+    assert_eq!(h.output("rms"), 0.5);
+    assert_eq!(h.output("rms"), h.module().rms(&h.rack));
+}
@@ -0,0 +1,135 @@
+use oscen::effects::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::reverb::*;
+
+#[test]
+fn flanger() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    // Without depth the delay stays at its 0.5 ms minimum, 2 samples here.
+    FlangerBuilder::new(c.tag())
+        .depth(0.0)
+        .feedback(0.5)
+        .mix(1.0)
+        .rack(&mut rack);
+    let mut rs = vec![rack.mono(4000.0)];
+    c.set_value(&mut rack, 0.0.into());
+    rs.extend((0..5).map(|_| rack.mono(4000.0)));
+    assert_eq!(rs, vec![0.0, 0.0, 1.0, 0.0, 0.5, 0.0]);
+}
+
+#[test]
+fn tremolo() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    TremoloBuilder::new(c.tag())
+        .rate(1.0)
+        .depth(1.0)
+        .stereo_phase(0.5)
+        .rack(&mut rack);
+    let rs: Vec<(f32, f32)> = (0..3).map(|_| rack.stereo(4.0)).collect();
+    let expected = [(1.0, 0.0), (0.5, 0.5), (0.0, 1.0)];
+    for ((l, r), (el, er)) in rs.iter().zip(expected) {
+        assert!((l - el).abs() < 1e-6 && (r - er).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn auto_wah() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let wah = AutoWahBuilder::new(c.tag())
+        .sensitivity(2.0)
+        .base_cutoff(200.0)
+        .range(2.0)
+        .rack(&mut rack);
+    rack.mono(44_100.0);
+    assert_eq!(wah.cutoff(&rack), 200.0);
+
+    // A loud input opens the filter by the full range, two octaves.
+    c.set_value(&mut rack, 1.0.into());
+    for _ in 0..4410 {
+        rack.mono(44_100.0);
+    }
+    assert!((wah.cutoff(&rack) - 800.0).abs() < 1e-3);
+
+    // And it closes again as the level falls away.
+    c.set_value(&mut rack, 0.0.into());
+    for _ in 0..44_100 {
+        rack.mono(44_100.0);
+    }
+    assert!((wah.cutoff(&rack) - 200.0).abs() < 1.0);
+}
+
+#[test]
+fn pitch_shifter() {
+    // Upward zero crossings per second of a 441 Hz sine shifted by `pitch`.
+    // The window is a whole number of input periods, so the jumps of the
+    // taps do not shift the phase of the output.
+    let hz = |pitch: f32| {
+        let mut rack = Rack::default();
+        let sine = OscBuilder::new(sine_osc).hz(441.0).rack(&mut rack);
+        PitchShifterBuilder::new(sine.tag())
+            .pitch(pitch)
+            .window(1000)
+            .rack(&mut rack);
+        let samples: Vec<f32> = (0..46_100).map(|_| rack.mono(44_100.0)).collect();
+        samples[1999..]
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count() as f32
+    };
+    assert!((hz(0.0) - 441.0).abs() <= 1.0);
+    assert!((hz(12.0) - 882.0).abs() <= 2.0);
+    assert!((hz(-12.0) - 220.5).abs() <= 2.0);
+}
+
+#[test]
+fn freeverb_freeze() {
+    let rms = |rack: &mut Rack, samples: usize| {
+        let sum: f32 = (0..samples).map(|_| rack.stereo(44_100.0).0.powi(2)).sum();
+        (sum / samples as f32).sqrt()
+    };
+    let tail = |freeze: bool| {
+        let mut rack = Rack::default();
+        let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+        let fv = FreeverbBuilder::mono(c.tag()).rack(&mut rack);
+        rms(&mut rack, 441);
+        c.set_value(&mut rack, 0.0.into());
+        rms(&mut rack, 4_410);
+        if freeze {
+            fv.set_freeze(&mut rack, 1.0.into());
+        }
+        rms(&mut rack, (FREEZE_TIME * 44_100.0) as usize);
+        let before = rms(&mut rack, 4_410);
+        // Input while frozen is not let in.
+        c.set_value(&mut rack, 1.0.into());
+        rms(&mut rack, 44_100);
+        (before, rms(&mut rack, 4_410), fv.frozen(&rack))
+    };
+    let (before, after, frozen) = tail(true);
+    assert_eq!(frozen, 1.0);
+    assert!((after / before - 1.0).abs() < 0.2, "{before} {after}");
+    let (before, after, frozen) = tail(false);
+    assert_eq!(frozen, 0.0);
+    assert!(after > before);
+}
+
+#[test]
+fn freeverb_sample_rate() {
+    // Seconds until the first echo comes out, whatever the sample rate.
+    let first_echo = |sample_rate: f32| {
+        let mut rack = Rack::default();
+        let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+        FreeverbBuilder::mono(c.tag()).rack(&mut rack);
+        rack.set_sample_rate(sample_rate);
+        let n = (0..sample_rate as usize)
+            .position(|_| rack.stereo(sample_rate).0 != 0.0)
+            .unwrap();
+        n as f32 / sample_rate
+    };
+    let t = first_echo(44_100.0);
+    assert!((t - 1116.0 / 44_100.0).abs() < 1e-3, "{t}");
+    assert!((first_echo(96_000.0) - t).abs() < 1e-4);
+}
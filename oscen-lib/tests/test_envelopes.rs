@@ -0,0 +1,165 @@
+use oscen::envelopes::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+
+#[test]
+fn looping_ad() {
+    let mut rack = Rack::default();
+    let adsr = AdsrBuilder::linear()
+        .attack(0.25)
+        .decay(0.25)
+        .sustain(0.0)
+        .mode(EnvelopeMode::LoopingAd)
+        .rack(&mut rack);
+    adsr.on(&mut rack);
+    let rs: Vec<f32> = (0..9).map(|_| rack.mono(8.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.5, 1.0, 0.5, 0.0, 0.5, 1.0, 0.5, 0.0]);
+}
+
+#[test]
+fn velocity() {
+    let mut rack = Rack::default();
+    let adsr = AdsrBuilder::linear()
+        .attack(0.25)
+        .sustain(1.0)
+        .velocity_sensitivity(0.5)
+        .rack(&mut rack);
+    adsr.on_with_velocity(&mut rack, 0.5);
+    let rs: Vec<f32> = (0..4).map(|_| rack.mono(8.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.375, 0.75, 0.75]);
+    adsr.set_velocity_sensitivity(&mut rack, 0.0.into());
+    assert_eq!(rack.mono(8.0), 1.0);
+}
+
+#[test]
+fn stage() {
+    let mut rack = Rack::default();
+    let adsr = AdsrBuilder::linear()
+        .attack(0.25)
+        .decay(0.25)
+        .sustain(0.5)
+        .release(0.25)
+        .rack(&mut rack);
+    adsr.on(&mut rack);
+    let mut stages = vec![];
+    for _ in 0..5 {
+        rack.mono(8.0);
+        stages.push(adsr.stage(&rack));
+    }
+    assert_eq!(adsr.level(&rack), 0.5);
+    adsr.off(&mut rack);
+    rack.mono(8.0);
+    stages.push(adsr.stage(&rack));
+    assert_eq!(
+        stages,
+        vec![
+            EnvelopeStage::Attack,
+            EnvelopeStage::Attack,
+            EnvelopeStage::Decay,
+            EnvelopeStage::Decay,
+            EnvelopeStage::Sustain,
+            EnvelopeStage::Release,
+        ]
+    );
+}
+
+#[test]
+fn breakpoint_envelope() {
+    let mut rack = Rack::default();
+    let env = BreakpointEnvelopeBuilder::new(&[
+        Breakpoint::linear(2.0, 1.0),
+        Breakpoint::linear(1.0, 0.5),
+        Breakpoint::linear(2.0, 0.0),
+    ])
+    .rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.0);
+    env.trigger(&mut rack);
+    let rs: Vec<f32> = (0..7).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.5, 1.0, 0.5, 0.25, 0.0, 0.0]);
+    assert!(!env.active(&rack));
+
+    // Curved segments bend towards their curve point.
+    let mut rack = Rack::default();
+    let gate = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let env = BreakpointEnvelopeBuilder::new(&[Breakpoint::new(2.0, 1.0, 0.2)])
+        .trigger_input(gate.tag())
+        .rack(&mut rack);
+    gate.set_value(&mut rack, 1.0.into());
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert!((rs[1] - 0.2).abs() < 1e-5);
+    assert_eq!(rs[2], 1.0);
+    assert_eq!(env.points().len(), 1);
+}
+
+#[test]
+fn breakpoint_envelope_loop() {
+    let mut rack = Rack::default();
+    let env = BreakpointEnvelopeBuilder::new(&[
+        Breakpoint::linear(1.0, 1.0),
+        Breakpoint::linear(2.0, 0.0),
+        Breakpoint::linear(2.0, 1.0),
+        Breakpoint::linear(1.0, 0.0),
+    ])
+    .loop_points(0, 2)
+    .rack(&mut rack);
+    env.trigger(&mut rack);
+    let rs: Vec<f32> = (0..9).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![0.0, 1.0, 0.5, 0.0, 0.5, 1.0, 0.5, 0.0, 0.5]);
+    env.release(&mut rack);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn fast_release() {
+    let mut rack = Rack::default();
+    let adsr = AdsrBuilder::linear()
+        .attack(0.25)
+        .sustain(1.0)
+        .release(10.0)
+        .rack(&mut rack);
+    adsr.on(&mut rack);
+    let rs: Vec<f32> = (0..4).map(|_| rack.mono(8.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.5, 1.0, 1.0]);
+    adsr.fast_release(&mut rack, 0.5);
+    assert_eq!(rack.mono(8.0), 0.75);
+    assert_eq!(adsr.stage(&rack), EnvelopeStage::Release);
+    // Retriggering waits for the fade to reach 0.
+    adsr.on(&mut rack);
+    let rs: Vec<f32> = (0..6).map(|_| rack.mono(8.0)).collect();
+    assert_eq!(rs, vec![0.5, 0.25, 0.0, 0.0, 0.5, 1.0]);
+    assert!(!adsr.fast_releasing(&rack));
+    // Without a retrigger the envelope stays idle.
+    adsr.fast_release(&mut rack, 0.125);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(8.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.0, 0.0]);
+    assert_eq!(adsr.stage(&rack), EnvelopeStage::Idle);
+}
+
+#[test]
+fn retrigger_velocity() {
+    let mut rack = Rack::default();
+    let adsr = AdsrBuilder::linear()
+        .attack(0.01)
+        .decay(0.01)
+        .sustain(0.5)
+        .velocity_sensitivity(1.0)
+        .rack(&mut rack);
+    let play = |rack: &mut Rack, samples: usize| -> Vec<f32> {
+        (0..samples).map(|_| rack.mono(44_100.0)).collect()
+    };
+    adsr.on(&mut rack);
+    let held = *play(&mut rack, 4_410).last().unwrap();
+    assert!((held - 0.5).abs() < 1e-6);
+    // Softer and then louder, the output carries on from where it was.
+    for (velocity, sustain) in [(0.2, 0.1), (0.8, 0.4)] {
+        adsr.on_with_velocity(&mut rack, velocity);
+        let mut rs = vec![rack.output(adsr.tag(), 0).unwrap()];
+        rs.extend(play(&mut rack, 4_410));
+        let jump = rs
+            .windows(2)
+            .fold(0.0, |m: f32, w| m.max((w[1] - w[0]).abs()));
+        assert!(jump < 0.01, "{jump}");
+        assert!((rs.last().unwrap() - sustain).abs() < 1e-4);
+    }
+}
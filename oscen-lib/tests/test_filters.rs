@@ -0,0 +1,185 @@
+use oscen::filters::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::testing::*;
+
+#[test]
+fn slew_limiter() {
+    let mut h = Harness::new(|rack, input| {
+        SlewLimiterBuilder::new(input)
+            .rise(0.25)
+            .fall(0.5)
+            .rack(rack)
+    })
+    .with_sample_rate(1.0);
+    h.set_input(1.0);
+    assert_eq!(h.run(5), vec![0.25, 0.5, 0.75, 1.0, 1.0]);
+    h.set_input(0.0);
+    h.set_control("fall", 0.5);
+    assert_eq!(h.run(3), vec![0.5, 0.0, 0.0]);
+}
+
+#[test]
+fn comb_filter() {
+    let mut h = Harness::new(|rack, input| {
+        CombFilterBuilder::new(input)
+            .delay(0.2)
+            .feedback(0.5)
+            .rack(rack)
+    })
+    .with_sample_rate(10.0);
+    let rs = h.run_input(&[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(rs, vec![0.0, 0.0, 1.0, 0.0, 0.5, 0.0, 0.25]);
+}
+
+#[test]
+fn allpass_filter() {
+    let mut h = Harness::new(|rack, input| {
+        AllpassFilterBuilder::new(input)
+            .delay(0.2)
+            .feedback(0.5)
+            .rack(rack)
+    })
+    .with_sample_rate(10.0);
+    let rs = h.run_input(&[1.0, 0.0, 0.0, 0.0, 0.0]);
+    assert_eq!(rs, vec![-0.5, 0.0, 0.75, 0.0, 0.375]);
+}
+
+#[test]
+fn delay_filters_tiny_sample_rate() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let comb = CombFilterBuilder::new(c.tag())
+        .max_delay(0.1)
+        .rack(&mut rack);
+    AllpassFilterBuilder::new(comb.tag()).rack(&mut rack);
+    rack.set_sample_rate(2.0);
+    for _ in 0..8 {
+        assert!(rack.mono(2.0).is_finite());
+    }
+}
+
+/// Frequency of the strongest partial of `xs` between `lo` and `hi` Hz.
+fn peak_hz(xs: &[f32], sample_rate: f32, lo: f32, hi: f32) -> f32 {
+    let mut best = (0.0, lo);
+    let mut hz = lo;
+    while hz < hi {
+        let w = 2.0 * std::f64::consts::PI * hz as f64 / sample_rate as f64;
+        let (re, im) = xs.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, x)| {
+            let x = *x as f64;
+            (re + x * (w * i as f64).cos(), im - x * (w * i as f64).sin())
+        });
+        let m = re * re + im * im;
+        if m > best.0 {
+            best = (m, hz);
+        }
+        hz += 0.02;
+    }
+    best.1
+}
+
+#[test]
+fn allpass_tuned_comb() {
+    let sample_rate = 44_100.0;
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    CombFilterBuilder::new(c.tag())
+        .delay(1.0 / 440.0)
+        .feedback(0.999)
+        .interpolation(InterpolationMode::Allpass)
+        .rack(&mut rack);
+    let mut xs = vec![rack.mono(sample_rate)];
+    c.set_value(&mut rack, 0.0.into());
+    xs.extend((0..44_100).map(|_| rack.mono(sample_rate)));
+    let hz = peak_hz(&xs, sample_rate, 436.0, 444.0);
+    let cents = 1200.0 * (hz / 440.0).log2();
+    assert!(cents.abs() < 1.0, "tuned to {} Hz, {} cents off", hz, cents);
+}
+
+/// Output of a biquad fed `input` after it has settled.
+fn biquad_settled(filter_type: FilterType, gain_db: f32, input: fn(f32, f32) -> f32) -> f32 {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(input).hz(1_000.0).rack(&mut rack);
+    BiquadBuilder::new(osc.tag(), filter_type)
+        .cutoff(1_000.0)
+        .gain_db(gain_db)
+        .rack(&mut rack);
+    (0..44_100)
+        .map(|_| rack.mono(44_100.0).abs())
+        .skip(40_000)
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn biquad() {
+    let dc = |_, _| 1.0;
+    let db = |x: f32| 20.0 * x.log10();
+    assert!((biquad_settled(FilterType::Lowpass, 0.0, dc) - 1.0).abs() < 1e-3);
+    assert!(biquad_settled(FilterType::Highpass, 0.0, dc) < 1e-3);
+    assert!((db(biquad_settled(FilterType::LowShelf, 6.0, dc)) - 6.0).abs() < 0.05);
+    assert!((biquad_settled(FilterType::HighShelf, 6.0, dc) - 1.0).abs() < 1e-3);
+    assert!((biquad_settled(FilterType::Bandpass, 0.0, sine_osc) - 1.0).abs() < 1e-2);
+    assert!(biquad_settled(FilterType::Notch, 0.0, sine_osc) < 1e-2);
+    assert!((db(biquad_settled(FilterType::Peaking, -6.0, sine_osc)) + 6.0).abs() < 0.1);
+}
+
+#[test]
+fn keytracking() {
+    // A 2 kHz tone through a 1 kHz lowpass, opened an octave by playing an
+    // octave above the reference, passes at the -3 dB point of the filter.
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc).hz(2_000.0).rack(&mut rack);
+    BiquadBuilder::new(osc.tag(), FilterType::Lowpass)
+        .cutoff(1_000.0)
+        .keytrack_hz(2.0 * KEYTRACK_REFERENCE)
+        .keytrack_amount(1.0)
+        .rack(&mut rack);
+    let peak = (0..44_100)
+        .map(|_| rack.mono(44_100.0).abs())
+        .skip(40_000)
+        .fold(0.0, f32::max);
+    assert!((peak - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-2);
+}
+
+#[test]
+fn formant_filter() {
+    let peak = |hz: f32, vowel: f32| {
+        let mut rack = Rack::default();
+        let osc = OscBuilder::new(sine_osc).hz(hz).rack(&mut rack);
+        FormantFilterBuilder::new(osc.tag())
+            .vowel(vowel)
+            .rack(&mut rack);
+        (0..44_100)
+            .map(|_| rack.mono(44_100.0).abs())
+            .skip(40_000)
+            .fold(0.0, f32::max)
+    };
+    // The first formant of "a" is at 600 Hz and of "i" at 250 Hz.
+    assert!(peak(600.0, 0.0) > 0.9);
+    assert!(peak(600.0, 2.0) < 0.2);
+    assert!(peak(250.0, 2.0) > 0.9);
+}
+
+#[test]
+fn one_pole() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let lp = OnePoleBuilder::new(c.tag()).cutoff(10.0).rack(&mut rack);
+    let hp = OnePoleBuilder::new(c.tag())
+        .mode(OnePoleMode::Highpass)
+        .cutoff(10.0)
+        .rack(&mut rack);
+    // After one time constant a step has risen to 1 - 1/e.
+    let samples = (44_100.0 / (2.0 * std::f32::consts::PI * 10.0)) as usize;
+    for _ in 0..samples {
+        rack.mono(44_100.0);
+    }
+    let lp_out = rack.outputs[(lp.tag(), 0)];
+    assert!((lp_out - (1.0 - (-1.0f32).exp())).abs() < 1e-3);
+    assert!((rack.outputs[(hp.tag(), 0)] - (1.0 - lp_out)).abs() < 1e-3);
+    // DC is blocked by the highpass.
+    for _ in 0..44_100 {
+        rack.mono(44_100.0);
+    }
+    assert!(rack.outputs[(hp.tag(), 0)].abs() < 1e-6);
+}
@@ -0,0 +1,39 @@
+use oscen::instruments::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+
+/// Largest absolute output over the next `n` samples.
+fn peak(rack: &mut Rack, n: usize) -> f32 {
+    (0..n).fold(0.0, |m, _| m.max(rack.mono(44_100.0).abs()))
+}
+
+#[test]
+fn wave_guide_transient() {
+    let mut rack = Rack::default();
+    let silence = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let wg = WaveGuideBuilder::new(silence.tag())
+        .transient(1.0)
+        .transient_decay(0.01)
+        .rack(&mut rack);
+    peak(&mut rack, 4_410);
+    assert_eq!(peak(&mut rack, 100), 0.0);
+    wg.on_with_velocity(&mut rack, 0.5);
+    let p = peak(&mut rack, 441);
+    assert!(p > 0.1 && p <= 0.5);
+    wg.off(&mut rack);
+    peak(&mut rack, 4_410);
+    assert_eq!(peak(&mut rack, 100), 0.0);
+}
+
+#[test]
+fn wave_guide_without_transient() {
+    let mut rack = Rack::default();
+    let silence = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let n = rack.num_modules();
+    WaveGuideBuilder::new(silence.tag()).rack(&mut rack);
+    let plain = rack.num_modules() - n;
+    WaveGuideBuilder::new(silence.tag())
+        .transient(1.0)
+        .rack(&mut rack);
+    assert_eq!(rack.num_modules() - n - plain, plain + 3);
+}
@@ -0,0 +1,340 @@
+use oscen::midi::*;
+use oscen::rack::*;
+
+#[test]
+fn midi_pitch_glide() {
+    let mut rack = Rack::default();
+    let mp = MidiPitchBuilder::new().glide(2.0).rack(&mut rack);
+    mp.note_on(&mut rack, 57.0);
+    assert_eq!(rack.mono(1f32).round(), 220.0);
+    // Legato: the second note is played while the first is held.
+    mp.note_on(&mut rack, 69.0);
+    let r1 = rack.mono(1f32).round();
+    let r2 = rack.mono(1f32).round();
+    let r3 = rack.mono(1f32).round();
+    assert_eq!((r1, r2, r3), (220.0, 330.0, 440.0));
+    // Not legato: jump straight to the new note.
+    mp.note_off(&mut rack);
+    mp.note_off(&mut rack);
+    // Nor when the step is set once every note is released.
+    mp.set_step(&mut rack, 45.0.into());
+    assert_eq!(rack.mono(1f32).round(), 110.0);
+    mp.note_on(&mut rack, 57.0);
+    assert_eq!(rack.mono(1f32).round(), 220.0);
+}
+
+#[test]
+fn midi_pitch_glide_always() {
+    let mut rack = Rack::default();
+    let mp = MidiPitchBuilder::new()
+        .glide(2.0)
+        .glide_mode(GlideMode::Always)
+        .rack(&mut rack);
+    mp.set_step(&mut rack, 57.0.into());
+    rack.mono(1f32);
+    mp.set_step(&mut rack, 69.0.into());
+    let r1 = rack.mono(1f32).round();
+    let r2 = rack.mono(1f32).round();
+    assert_eq!((r1, r2), (220.0, 330.0));
+}
+
+#[test]
+fn voice_allocator_steal_oldest() {
+    let mut va: VoiceAllocator<2> = VoiceAllocator::new(VoiceStealMode::Oldest, NotePriority::Last);
+    assert_eq!(
+        va.note_on(60),
+        Some(VoiceCommand::On { voice: 0, note: 60 })
+    );
+    assert_eq!(
+        va.note_on(62),
+        Some(VoiceCommand::On { voice: 1, note: 62 })
+    );
+    assert_eq!(
+        va.note_on(64),
+        Some(VoiceCommand::On { voice: 0, note: 64 })
+    );
+    // 60 was stolen but is still held, so it takes over the released voice.
+    assert_eq!(
+        va.note_off(62),
+        Some(VoiceCommand::On { voice: 1, note: 60 })
+    );
+    assert_eq!(va.note_off(60), Some(VoiceCommand::Off { voice: 1 }));
+    assert_eq!(va.note_off(60), None);
+}
+
+#[test]
+fn voice_allocator_steal_quietest() {
+    let mut va: VoiceAllocator<2> =
+        VoiceAllocator::new(VoiceStealMode::Quietest, NotePriority::Last);
+    va.note_on(60);
+    va.note_on(62);
+    va.set_level(0, 0.8);
+    va.set_level(1, 0.2);
+    assert_eq!(
+        va.note_on(64),
+        Some(VoiceCommand::On { voice: 1, note: 64 })
+    );
+}
+
+#[test]
+fn voice_allocator_no_steal() {
+    let mut va: VoiceAllocator<1> = VoiceAllocator::new(VoiceStealMode::None, NotePriority::Last);
+    va.note_on(60);
+    assert_eq!(va.note_on(62), None);
+    assert_eq!(
+        va.note_off(60),
+        Some(VoiceCommand::On { voice: 0, note: 62 })
+    );
+}
+
+#[test]
+fn voice_allocator_mono_priority() {
+    let mut va: VoiceAllocator<1> =
+        VoiceAllocator::new(VoiceStealMode::Oldest, NotePriority::Lowest);
+    va.note_on(60);
+    assert_eq!(va.note_on(64), None);
+    assert_eq!(
+        va.note_on(55),
+        Some(VoiceCommand::On { voice: 0, note: 55 })
+    );
+    assert_eq!(
+        va.note_off(55),
+        Some(VoiceCommand::On { voice: 0, note: 60 })
+    );
+
+    let mut va: VoiceAllocator<1> =
+        VoiceAllocator::new(VoiceStealMode::Oldest, NotePriority::First);
+    va.note_on(60);
+    assert_eq!(va.note_on(64), None);
+    assert_eq!(va.voice(60), Some(0));
+}
+
+#[test]
+fn midi_message() {
+    assert_eq!(
+        MidiMessage::from_bytes(&[0x91, 60, 100]),
+        Some(MidiMessage::NoteOn {
+            channel: 1,
+            note: 60,
+            velocity: 100
+        })
+    );
+    assert_eq!(
+        MidiMessage::from_bytes(&[0x90, 60, 0]),
+        Some(MidiMessage::NoteOff {
+            channel: 0,
+            note: 60,
+            velocity: 0
+        })
+    );
+    let pedal = MidiMessage::from_bytes(&[0xB0, SUSTAIN_PEDAL, 127]).unwrap();
+    assert_eq!(pedal.sustain(), Some(true));
+    assert_eq!(MidiMessage::from_bytes(&[0xFE]), None);
+}
+
+#[test]
+fn voice_allocator_sustain() {
+    let mut va: VoiceAllocator<4> = VoiceAllocator::default();
+    va.message(MidiMessage::from_bytes(&[0xB0, SUSTAIN_PEDAL, 127]).unwrap());
+    va.message(MidiMessage::from_bytes(&[0x90, 60, 100]).unwrap());
+    va.message(MidiMessage::from_bytes(&[0x90, 64, 100]).unwrap());
+    assert!(va
+        .message(MidiMessage::from_bytes(&[0x80, 60, 0]).unwrap())
+        .is_empty());
+    assert!(va
+        .message(MidiMessage::from_bytes(&[0x80, 64, 0]).unwrap())
+        .is_empty());
+    assert_eq!(va.voice(60), Some(0));
+    let released = va.message(MidiMessage::from_bytes(&[0xB0, SUSTAIN_PEDAL, 0]).unwrap());
+    assert_eq!(
+        released.collect::<Vec<_>>(),
+        vec![
+            VoiceCommand::Off { voice: 0 },
+            VoiceCommand::Off { voice: 1 }
+        ]
+    );
+    assert_eq!(va.voice(60), None);
+}
+
+#[test]
+fn midi_cc_parser() {
+    let mut rack = Rack::default();
+    let cc = MidiCcParserBuilder::new(&[1, 74]).rack(&mut rack);
+    cc.message(&mut rack, &[0xB0, 74, 127]);
+    cc.message(&mut rack, &[0xB0, 7, 127]);
+    let out = rack.play(1f32);
+    assert_eq!((out[0], out[1]), (0.0, 1.0));
+    assert_eq!(cc.value(&rack, 74), Some(1.0));
+    assert_eq!(cc.value(&rack, 7), None);
+    assert!(matches!(cc.output(74), Some(Control::V(_, 1))));
+}
+
+#[test]
+fn tuning() {
+    let et = EqualTemperament::default();
+    assert_eq!(et.frequency(69), 440.0);
+    assert_eq!(et.frequency(57).round(), 220.0);
+    let et19 = EqualTemperament::new(19.0);
+    assert_eq!(et19.frequency(88).round(), 880.0);
+
+    let scl = "! just.scl\nJust major\n 3\n5/4\n701.955\n2/1\n";
+    let just = ScalaTuning::from_scl(scl, 60, 200.0).unwrap();
+    assert_eq!(just.frequency(60), 200.0);
+    assert_eq!(just.frequency(61), 250.0);
+    assert_eq!(just.frequency(62).round(), 300.0);
+    assert_eq!(just.frequency(63), 400.0);
+    assert_eq!(just.frequency(59).round(), 150.0);
+    assert!(ScalaTuning::from_scl("bad\n2\n3/2\n", 60, 200.0).is_err());
+
+    let mut rack = Rack::default();
+    MidiPitchBuilder::new()
+        .tuning(just)
+        .step(61.0)
+        .rack(&mut rack);
+    assert_eq!(rack.mono(1f32), 250.0);
+}
+
+#[test]
+fn quantizer() {
+    let mut rack = Rack::default();
+    let q = QuantizerBuilder::new(Scale::major())
+        .pitch(61.4)
+        .root(2.0)
+        .rack(&mut rack);
+    // D major: 61 (C#) is in the scale.
+    assert_eq!(rack.mono(1.0), 61.0);
+    q.set_pitch(&mut rack, 60.4.into());
+    assert_eq!(rack.mono(1.0), 61.0);
+    q.set_pitch(&mut rack, 59.6.into());
+    assert_eq!(rack.mono(1.0), 59.0);
+    q.set_root(&mut rack, 0.0.into());
+    q.set_pitch(&mut rack, 70.0.into());
+    assert_eq!(rack.mono(1.0), 69.0);
+    assert_eq!(Scale::minor_pentatonic().quantize(-0.5, 0.0), 0.0);
+    assert_eq!(Scale::new(&[]), Scale::chromatic());
+}
+
+#[test]
+fn merge_midi_messages() {
+    let on = |note| MidiMessage::NoteOn {
+        channel: 0,
+        note,
+        velocity: 100,
+    };
+    let sequencer = [
+        TimedMidiMessage::new(0, on(60)),
+        TimedMidiMessage::new(32, on(62)),
+    ];
+    let live = [
+        TimedMidiMessage::new(16, on(72)),
+        TimedMidiMessage::new(32, on(74)),
+    ];
+    let merged = merge_midi([&sequencer, &live]);
+    let notes: Vec<(usize, MidiMessage)> =
+        merged.iter().map(|m| (m.frame_offset, m.message)).collect();
+    assert_eq!(
+        notes,
+        vec![(0, on(60)), (16, on(72)), (32, on(62)), (32, on(74))]
+    );
+
+    let mut allocator = VoiceAllocator::<2>::default();
+    for m in merged {
+        allocator.message(m.message);
+    }
+    assert_eq!(allocator.note(0), Some(62));
+    assert_eq!(allocator.note(1), Some(74));
+}
+
+#[test]
+fn aftertouch() {
+    assert_eq!(
+        MidiMessage::from_bytes(&[0xA2, 60, 90]),
+        Some(MidiMessage::PolyPressure {
+            channel: 2,
+            note: 60,
+            pressure: 90
+        })
+    );
+    assert_eq!(
+        MidiMessage::from_bytes(&[0xD0, 64]),
+        Some(MidiMessage::ChannelPressure {
+            channel: 0,
+            pressure: 64
+        })
+    );
+
+    let mut va: VoiceAllocator<2> = VoiceAllocator::default();
+    va.note_on(60);
+    va.note_on(64);
+    assert_eq!(
+        va.message(MidiMessage::from_bytes(&[0xA0, 64, 90]).unwrap())
+            .collect::<Vec<_>>(),
+        vec![VoiceCommand::Pressure {
+            voice: 1,
+            pressure: 90
+        }]
+    );
+    assert_eq!(va.pressure(67, 90), None);
+
+    let mut rack = Rack::default();
+    let pressure = MidiPressureBuilder::new().rack(&mut rack);
+    pressure.message(&mut rack, &[0xA0, 64, 90]);
+    assert_eq!(rack.mono(44_100.0), 0.0);
+    pressure.message(&mut rack, &[0xD0, 127]);
+    assert_eq!(rack.mono(44_100.0), 1.0);
+    assert_eq!(pressure.value(&rack), 1.0);
+}
+
+#[test]
+fn midi_clock() {
+    assert_eq!(MidiMessage::from_bytes(&[0xF8]), Some(MidiMessage::Clock));
+    let mut rack = Rack::default();
+    let clock = MidiClockBuilder::new().rack(&mut rack);
+    assert_eq!(clock.message(&mut rack, &[0xFA]), Some(Transport::Start));
+    // A tick every 1000 samples at 48 kHz is a quarter note every 0.5 s.
+    for _ in 0..48 {
+        assert_eq!(clock.message(&mut rack, &[0xF8]), None);
+        for _ in 0..1000 {
+            rack.mono(48_000.0);
+        }
+    }
+    assert!((clock.bpm(&rack) - 120.0).abs() < 1e-3);
+    assert!(clock.running(&rack));
+    assert_eq!(rack.outputs[(clock.tag(), 1)], 1.0);
+    assert_eq!(clock.ticks(&rack), 48);
+
+    // The estimate follows a change of tempo.
+    for _ in 0..200 {
+        clock.message(&mut rack, &[0xF8]);
+        for _ in 0..800 {
+            rack.mono(48_000.0);
+        }
+    }
+    assert!((clock.bpm(&rack) - 150.0).abs() < 0.1);
+
+    assert_eq!(clock.message(&mut rack, &[0xFC]), Some(Transport::Stop));
+    assert!(!clock.running(&rack));
+}
+
+#[test]
+fn voice_allocator_steal_message() {
+    let mut va: VoiceAllocator<2> = VoiceAllocator::default();
+    let on = |note| MidiMessage::from_bytes(&[0x90, note, 100]).unwrap();
+    va.message(on(60));
+    assert_eq!(
+        va.message(on(62)).collect::<Vec<_>>(),
+        vec![VoiceCommand::On { voice: 1, note: 62 }]
+    );
+    assert_eq!(
+        va.message(on(64)).collect::<Vec<_>>(),
+        vec![
+            VoiceCommand::Steal { voice: 0 },
+            VoiceCommand::On { voice: 0, note: 64 }
+        ]
+    );
+    // Retriggering the note a voice is playing is not a steal.
+    assert_eq!(
+        va.message(on(64)).collect::<Vec<_>>(),
+        vec![VoiceCommand::On { voice: 0, note: 64 }]
+    );
+}
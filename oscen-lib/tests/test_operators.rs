@@ -71,3 +71,273 @@ fn modulator() {
     let r = rack.mono(1f32);
     assert_eq!(r, 3740.0);
 }
+
+#[test]
+fn ring_modulator() {
+    let mut rack = Rack::default();
+    let a = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let b = ConstBuilder::new((-1.0).into()).rack(&mut rack);
+    let rm = RingModulatorBuilder::new(a.tag(), b.tag()).rack(&mut rack);
+    assert_eq!(rack.mono(1.0), -0.5);
+    rm.set_mix(&mut rack, 0.5.into());
+    assert_eq!(rack.mono(1.0), 0.0);
+}
+
+#[test]
+fn amplitude_modulator() {
+    let mut rack = Rack::default();
+    let a = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let b = ConstBuilder::new((-1.0).into()).rack(&mut rack);
+    let am = AmplitudeModulatorBuilder::new(a.tag(), b.tag()).rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.0);
+    am.set_depth(&mut rack, 0.5.into());
+    assert_eq!(rack.mono(1.0), 0.25);
+    b.set_value(&mut rack, 1.0.into());
+    assert_eq!(rack.mono(1.0), 0.5);
+}
+
+#[test]
+fn vca_db() {
+    let mut rack = Rack::default();
+    let a = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let vca = VcaBuilder::new(a.tag()).level(0.5).rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.25);
+    vca.set_db(&mut rack, 20.0.into());
+    assert!((rack.mono(1.0) - 2.5).abs() < 1e-5);
+    vca.set_db(&mut rack, MIN_DB.into());
+    assert_eq!(rack.mono(1.0), 0.0);
+}
+
+#[test]
+fn delay_denormal() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1e-30.into()).rack(&mut rack);
+    DelayBuilder::new(c.tag(), 0.0.into()).rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 0.0);
+    c.set_value(&mut rack, 0.5.into());
+    assert_eq!(rack.mono(1.0), 0.5);
+}
+
+#[test]
+fn sub_rack() {
+    let mut inner = Rack::default();
+    let c = ConstBuilder::new(3.0.into()).rack(&mut inner);
+    let vca = VcaBuilder::new(c.tag()).level(2.0).rack(&mut inner);
+    let inputs = [(vca.tag(), 0)];
+    let outputs = [(vca.tag(), 0), (c.tag(), 0)];
+
+    let mut rack = Rack::default();
+    let level = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let sub = SubRackBuilder::new(inner, &inputs, &outputs).rack(&mut rack);
+    assert_eq!(sub.input(&rack, 0), 2.0);
+    assert_eq!(rack.mono(1.0), 6.0);
+    sub.set_input(&mut rack, 0, level.tag().into());
+    rack.mono(1.0);
+    assert_eq!(rack.output(sub.tag(), 0), Some(1.5));
+    assert_eq!(rack.output(sub.tag(), 1), Some(3.0));
+    assert_eq!(sub.inner().num_modules(), 2);
+
+    sub.set_input(&mut rack, 0, 2.0.into());
+    let guard = sub.inner();
+    rack.mono(1.0);
+    assert_eq!(rack.output(sub.tag(), 0), Some(1.5));
+    drop(guard);
+    rack.mono(1.0);
+    assert_eq!(rack.output(sub.tag(), 0), Some(6.0));
+}
+
+#[test]
+fn mod_matrix() {
+    let mut rack = Rack::default();
+    let lfo = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let env = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let mm = ModMatrixBuilder::new(vec![lfo.tag().into(), env.tag().into()], 3)
+        .depth(0, 0, 1.0.into())
+        .depth(1, 0, 0.5.into())
+        .depth(1, 2, (-1.0).into())
+        .rack(&mut rack);
+    rack.mono(1.0);
+    assert_eq!(rack.output(mm.tag(), 0), Some(1.5));
+    assert_eq!(rack.output(mm.tag(), 1), Some(0.0));
+    assert_eq!(rack.output(mm.tag(), 2), Some(-2.0));
+    mm.set_depth(&mut rack, 0, 1, 4.0.into());
+    rack.mono(1.0);
+    assert_eq!(rack.output(mm.tag(), 1), Some(2.0));
+    assert_eq!(mm.depth(&rack, 1, 0), 0.5);
+}
+
+#[test]
+fn cross_equal_power() {
+    let mut rack = Rack::default();
+    let c2 = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let c3 = ConstBuilder::new(3.0.into()).rack(&mut rack);
+    let cf = CrossFadeBuilder::new(c2.tag(), c3.tag())
+        .equal_power(true)
+        .rack(&mut rack);
+    let r = rack.mono(1f32);
+    assert!((r - 5.0 * std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    cf.set_alpha(&mut rack, 1.0.into());
+    assert!((rack.mono(1f32) - 3.0).abs() < 1e-6);
+}
+
+#[test]
+fn stereo_out() {
+    let mut rack = Rack::default();
+    let l = ConstBuilder::new(0.25.into()).rack(&mut rack);
+    let r = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let out = StereoOutBuilder::new((l.tag(), 0), (r.tag(), 0))
+        .level(2.0)
+        .rack(&mut rack);
+    assert_eq!(rack.stereo(1.0), (0.5, 1.0));
+    StereoOutBuilder::stereo(out.tag()).rack(&mut rack);
+    assert_eq!(rack.stereo(1.0), (0.5, 1.0));
+    StereoOutBuilder::mono(l.tag()).rack(&mut rack);
+    assert_eq!(rack.stereo(1.0), (0.25, 0.25));
+    assert_eq!(
+        rack.output_by_name(out.tag(), "right"),
+        Some(out.tag().right())
+    );
+}
+
+#[test]
+fn delay_set_sample_rate() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let delay = DelayBuilder::new(c.tag(), 0.75.into()).rack(&mut rack);
+    // The delay line is built for 44.1 kHz, too short for 0.75 s at 88.2 kHz.
+    rack.set_sample_rate(88_200.0);
+    assert_eq!(rack.buffers.buffers(delay.tag()).len(), 88_200);
+    let rs: Vec<f32> = (0..66_151).map(|_| rack.mono(88_200.0)).collect();
+    assert_eq!(rs[66_148], 0.0);
+    assert_eq!(rs[66_150], 1.0);
+}
+
+#[test]
+fn scale_offset() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(|x, _| 2.0 * x - 1.0)
+        .hz(1.0)
+        .rack(&mut rack);
+    let so = ScaleOffsetBuilder::new(lfo.tag())
+        .scale(100.0)
+        .offset(200.0)
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![100.0, 150.0, 200.0]);
+    so.set_offset(&mut rack, lfo.tag().into());
+    assert_eq!(rack.mono(4.0), 50.0 + 0.5);
+}
+
+#[test]
+fn delay_max_time() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let delay = DelayBuilder::new(c.tag(), 3.0.into())
+        .max_time(0.001)
+        .rack(&mut rack);
+    assert_eq!(delay.max_time(), 0.001);
+    assert_eq!(rack.buffers.buffers(delay.tag()).len(), 45);
+    // 3 s is clamped to what the 45 sample buffer can hold.
+    let rs: Vec<f32> = (0..45).map(|_| rack.mono(44_100.0)).collect();
+    assert_eq!(rs[42], 0.0);
+    assert_eq!(rs[43], 1.0);
+
+    rack.set_sample_rate(88_200.0);
+    assert_eq!(rack.buffers.buffers(delay.tag()).len(), 89);
+}
+
+#[test]
+fn delay_interpolation() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let delay = DelayBuilder::new(c.tag(), 0.375.into())
+        .interpolation(InterpolationMode::Linear)
+        .rack(&mut rack);
+    assert_eq!(
+        rack.buffers.buffers(delay.tag()).interpolation(),
+        InterpolationMode::Linear
+    );
+    // An impulse delayed by 1.5 samples lands half on each neighbour.
+    let mut rs = vec![rack.mono(4.0)];
+    c.set_value(&mut rack, 0.0.into());
+    rs.extend((0..3).map(|_| rack.mono(4.0)));
+    assert_eq!(rs, vec![0.0, 0.5, 0.5, 0.0]);
+}
+
+#[test]
+fn delay_allpass_interpolation() {
+    // Half a sample of delay on a sine at a quarter of the sample rate: a
+    // linear read loses 3 dB, an allpass read keeps the level.
+    let level = |mode: InterpolationMode| {
+        let mut rack = Rack::default();
+        let sine = OscBuilder::new(sine_osc).hz(1.0).rack(&mut rack);
+        DelayBuilder::new(sine.tag(), 0.625.into())
+            .interpolation(mode)
+            .rack(&mut rack);
+        let rs: Vec<f32> = (0..400).map(|_| rack.mono(4.0)).collect();
+        // The amplitude, from the mean square of the settled output.
+        (2.0 * rs[200..].iter().map(|x| x * x).sum::<f32>() / 200.0).sqrt()
+    };
+    assert!((level(InterpolationMode::Linear) - 0.5f32.sqrt()).abs() < 1e-3);
+    assert!((level(InterpolationMode::Allpass) - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn mixer_taps() {
+    let mut rack = Rack::default();
+    let voices: Vec<Tag> = [0.25, 0.5, 0.0]
+        .iter()
+        .map(|v| ConstBuilder::new((*v).into()).rack(&mut rack).tag())
+        .collect();
+    let mixer = MixerBuilder::new(voices).taps(true).rack(&mut rack);
+    let out = rack.play(1.0);
+    assert_eq!(out[..4], [0.75, 0.25, 0.5, 0.0]);
+    assert!(matches!(mixer.tap(1), Some(Control::V(t, 2)) if t == mixer.tag()));
+    assert!(mixer.tap(3).is_none());
+}
+
+#[test]
+fn gate_trigger_conversion() {
+    let mut rack = Rack::default();
+    let gate = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let trigger = GateToTriggerBuilder::new(gate.tag()).rack(&mut rack);
+    let long_gate = TriggerToGateBuilder::new(trigger.tag())
+        .length(3.0)
+        .rack(&mut rack);
+    let play = |rack: &mut Rack| {
+        rack.play(1.0);
+        (
+            rack.outputs[(trigger.tag(), 0)],
+            rack.outputs[(long_gate.tag(), 0)],
+        )
+    };
+    assert_eq!(play(&mut rack), (0.0, 0.0));
+    gate.set_value(&mut rack, 1.0.into());
+    let rs: Vec<(f32, f32)> = (0..5).map(|_| play(&mut rack)).collect();
+    assert_eq!(
+        rs,
+        vec![(1.0, 1.0), (0.0, 1.0), (0.0, 1.0), (0.0, 0.0), (0.0, 0.0)]
+    );
+}
+
+#[test]
+fn vca_clipped() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.8.into()).rack(&mut rack);
+    let vca = VcaBuilder::new(c.tag()).rack(&mut rack);
+    rack.mono(44_100.0);
+    assert!(!vca.clipped(&rack));
+    vca.set_db(&mut rack, 6.0.into());
+    rack.mono(44_100.0);
+    vca.set_db(&mut rack, 0.0.into());
+    rack.mono(44_100.0);
+    // The clip is held until it is cleared.
+    assert!(vca.clipped(&rack));
+    assert_eq!(
+        rack.output_by_name(vca.tag(), "clipped"),
+        Some((vca.tag(), 1))
+    );
+    vca.clear_clipped(&mut rack);
+    rack.mono(44_100.0);
+    assert!(!vca.clipped(&rack));
+}
@@ -1,3 +1,4 @@
+use oscen::operators::*;
 use oscen::oscillators::*;
 use oscen::rack::*;
 
@@ -11,6 +12,8 @@ fn osc() {
     let r2 = rack.mono(1f32);
     let r3 = rack.mono(1f32);
     assert_eq!((r1, r2, r3), (7.0, 7.5, 7.0));
+    assert_eq!(rack.output_by_name(o.tag(), "out"), Some((o.tag(), 0)));
+    assert_eq!(rack.output_by_name(o.tag(), "phase"), None);
 }
 
 #[test]
@@ -31,3 +34,219 @@ fn clock() {
     let r4 = rack.mono(1f32);
     assert_eq!((r1, r2, r3, r4), (1.0, 0.0, 0.0, 1.0));
 }
+
+// A connected input that is 0 hz must stop the oscillator rather than fall
+// back to some default frequency.
+#[test]
+fn zero_hz_input() {
+    let mut rack = Rack::default();
+    let hz = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let saw = OscBuilder::new(|x, _| x)
+        .phase(0.25)
+        .hz(Control::V(hz.tag(), 0))
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.25, 0.25, 0.25]);
+    // Negative frequencies run the phase backwards through zero.
+    hz.set_value(&mut rack, (-1.0).into());
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.25, 0.0, -0.25]);
+    assert_eq!(saw.hz(&rack), -1.0);
+}
+
+#[test]
+fn phase_mod() {
+    let mut rack = Rack::default();
+    let pm = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    OscBuilder::new(|x, _| x)
+        .hz(1.0)
+        .phase_mod(Control::V(pm.tag(), 0))
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..2).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.25]);
+    pm.set_value(&mut rack, 0.5.into());
+    let rs: Vec<f32> = (0..2).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.25]);
+}
+
+#[test]
+fn supersaw() {
+    let mut rack = Rack::default();
+    let saw = SuperSawBuilder::new()
+        .hz(1.0)
+        .detune(0.0)
+        .mix(0.0)
+        .random_phases(false)
+        .rack(&mut rack);
+    // Without detune all voices stay in phase.
+    let gain = 0.99785 + 6.0 * 0.044372;
+    let rs: Vec<f32> = (0..4).map(|_| rack.mono(4.0)).collect();
+    let expected: Vec<f32> = [0.0, 0.25, 0.5, 0.75]
+        .iter()
+        .map(|p| gain * saw_osc(*p, 0.0))
+        .collect();
+    for (r, e) in rs.iter().zip(expected) {
+        assert!((r - e).abs() < 1e-5);
+    }
+    saw.set_detune(&mut rack, 1.0.into());
+    saw.set_mix(&mut rack, 1.0.into());
+    for _ in 0..100 {
+        assert!(rack.mono(44100.0).abs() <= 0.44419 + 6.0 * 0.590862 + 1e-4);
+    }
+}
+
+#[test]
+fn phase_output() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc)
+        .hz(1.0)
+        .phase_output(true)
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..5)
+        .map(|_| {
+            rack.mono(4.0);
+            rack.outputs[(osc.tag(), 1)]
+        })
+        .collect();
+    assert_eq!(rs, vec![0.0, 0.25, 0.5, 0.75, 0.0]);
+    assert_eq!(
+        rack.output_by_name(osc.tag(), "phase"),
+        Some((osc.tag(), 1))
+    );
+    assert_eq!(rack.output_by_name(osc.tag(), "sync"), None);
+}
+
+#[test]
+fn additive_osc() {
+    let mut rack = Rack::default();
+    let osc = AdditiveOscBuilder::new(&[1.0.into(), 0.5.into()])
+        .hz(1.0)
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(8.0)).collect();
+    let expected = [0.0, std::f32::consts::FRAC_1_SQRT_2 + 0.5, 1.0];
+    for (r, e) in rs.iter().zip(expected) {
+        assert!((r - e).abs() < 1e-5);
+    }
+    // The second partial at 6 Hz is above the Nyquist frequency.
+    rack.reset();
+    osc.set_hz(&mut rack, 3.0.into());
+    rack.mono(8.0);
+    assert!((rack.mono(8.0) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-5);
+}
+
+#[test]
+fn fm_operator() {
+    let mut rack = Rack::default();
+    let modulator = FmOperatorBuilder::new()
+        .hz(1.0)
+        .ratio(2.0)
+        .level(0.25)
+        .rack(&mut rack);
+    let carrier = FmOperatorBuilder::new()
+        .hz(1.0)
+        .phase_mod(modulator.tag())
+        .rack(&mut rack);
+    // Phases in cycles: the modulator runs at 2 Hz and the carrier at 1 Hz.
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(8.0)).collect();
+    let expected: Vec<f32> = (0..3)
+        .map(|i| {
+            let m = 0.25 * sine_osc(i as f32 * 0.25, 0.0);
+            sine_osc(i as f32 * 0.125 + m, 0.0)
+        })
+        .collect();
+    for (r, e) in rs.iter().zip(expected) {
+        assert!((r - e).abs() < 1e-5);
+    }
+    carrier.set_feedback(&mut rack, 1.0.into());
+    for _ in 0..1000 {
+        assert!(rack.mono(44100.0).abs() <= 1.0);
+    }
+}
+
+#[test]
+fn sync_output() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc)
+        .hz(1.0)
+        .sync_output(true)
+        .rack(&mut rack);
+    let rs: Vec<f32> = (0..9)
+        .map(|_| {
+            rack.mono(4.0);
+            rack.outputs[(osc.tag(), 2)]
+        })
+        .collect();
+    assert_eq!(rs, vec![0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    // Only the outputs that are written have names.
+    assert_eq!(rack.output_by_name(osc.tag(), "sync"), Some((osc.tag(), 2)));
+    assert_eq!(rack.output_by_name(osc.tag(), "phase"), None);
+}
+
+#[test]
+fn seeded_noise() {
+    let render = |seed: u64| {
+        let mut rack = Rack::default().with_seed(seed);
+        let white = WhiteNoiseBuilder::new().rack(&mut rack);
+        let pink = PinkNoiseBuilder::new().rack(&mut rack);
+        MixerBuilder::new(vec![white.tag(), pink.tag()]).rack(&mut rack);
+        let rs: Vec<f32> = (0..64).map(|_| rack.mono(44_100.0)).collect();
+        (rack, rs)
+    };
+    let (mut rack, rs) = render(7);
+    assert_eq!(rs, render(7).1);
+    assert_ne!(rs, render(8).1);
+    // Reset replays the sequence from the seed.
+    rack.reset();
+    let again: Vec<f32> = (0..64).map(|_| rack.mono(44_100.0)).collect();
+    assert_eq!(rs, again);
+
+    // A seed given to the builder wins over the rack's.
+    let noise = |rack_seed: u64| {
+        let mut rack = Rack::default().with_seed(rack_seed);
+        WhiteNoiseBuilder::new().seed(1).rack(&mut rack);
+        rack.mono(44_100.0)
+    };
+    assert_eq!(noise(2), noise(3));
+}
+
+#[test]
+fn constant() {
+    let mut rack = Rack::default();
+    let c = ConstantBuilder::new(0.25).rack(&mut rack);
+    assert_eq!(c.value(), 0.25);
+    assert!(c.control_names().is_empty());
+    assert_eq!(rack.mono(44_100.0), 0.25);
+    rack.reset();
+    assert_eq!(rack.mono(44_100.0), 0.25);
+}
+
+#[test]
+fn poly_blep_osc() {
+    let render = |osc: &mut PolyBlepOscBuilder| {
+        let mut rack = Rack::default();
+        let o = osc.hz(100.0).rack(&mut rack);
+        let xs: Vec<f32> = (0..44_100).map(|_| rack.mono(44_100.0)).collect();
+        (xs, rack, o)
+    };
+    let mean = |xs: &[f32]| xs.iter().sum::<f32>() / xs.len() as f32;
+    let jump = |xs: &[f32]| {
+        xs.windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max)
+    };
+    let (saw, ..) = render(&mut PolyBlepOscBuilder::new(BlepWaveform::Saw));
+    assert!(mean(&saw).abs() < 1e-3);
+    // The step of 2 at the end of each cycle is spread over two samples.
+    assert!(jump(&saw) < 1.5);
+    let (pulse, mut rack, osc) =
+        render(PolyBlepOscBuilder::new(BlepWaveform::Pulse).pulse_width(0.25));
+    assert!((mean(&pulse) + 0.5).abs() < 1e-2);
+    assert!(jump(&pulse) < 1.5);
+    osc.set_pulse_width(&mut rack, 0.75.into());
+    let pulse: Vec<f32> = (0..44_100).map(|_| rack.mono(44_100.0)).collect();
+    assert!((mean(&pulse) - 0.5).abs() < 1e-2);
+    // Each cycle adds up to one impulse.
+    let (impulses, ..) = render(&mut PolyBlepOscBuilder::new(BlepWaveform::Impulse));
+    assert!((impulses.iter().sum::<f32>() - 100.0).abs() < 0.1);
+    assert!(impulses.iter().all(|x| (-0.01..=1.0).contains(x)));
+}
@@ -0,0 +1,542 @@
+use oscen::filters::*;
+use oscen::operators::*;
+use oscen::oscillators::*;
+use oscen::rack::*;
+
+#[test]
+fn introspection() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(sine_osc).hz(2.0).rack(&mut rack);
+    let osc = OscBuilder::new(saw_osc)
+        .amplitude(lfo.tag())
+        .rack(&mut rack);
+    let lpf = LpfBuilder::new(osc.tag()).rack(&mut rack);
+    let modules: Vec<ModuleInfo> = rack.modules().collect();
+    assert_eq!(modules.len(), 3);
+    assert_eq!(modules[2].tag, lpf.tag());
+    assert!(modules[2].type_name.ends_with("Lpf"));
+    let connections: Vec<Connection> = rack.connections().collect();
+    assert_eq!(
+        connections,
+        vec![Connection {
+            from: (lfo.tag(), 0),
+            to: (osc.tag(), 1)
+        }]
+    );
+}
+
+#[test]
+fn remove() {
+    let mut rack = Rack::default();
+    let c2 = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let c3 = ConstBuilder::new(3.0.into()).rack(&mut rack);
+    let last = ConstBuilder::new(c3.tag().into()).rack(&mut rack);
+    assert_eq!(rack.mono(1f32), 3.0);
+    assert!(rack.remove(c3.tag()));
+    assert!(!rack.remove(c3.tag()));
+    assert_eq!(rack.modules().count(), 2);
+    assert_eq!(rack.connections().count(), 0);
+    assert_eq!(rack.mono(1f32), 0.0);
+    assert_eq!(rack.output(c2.tag(), 0), Some(2.0));
+    assert!(rack.remove(last.tag()));
+    rack.mono(1f32);
+    assert_eq!(rack.output(last.tag(), 0), None);
+}
+
+#[test]
+fn reset() {
+    let mut rack = Rack::default();
+    OscBuilder::new(|x, _| x).hz(0.25).rack(&mut rack);
+    let first: Vec<f32> = (0..3).map(|_| rack.mono(1f32)).collect();
+    rack.reset();
+    let second: Vec<f32> = (0..3).map(|_| rack.mono(1f32)).collect();
+    assert_eq!(first, vec![0.0, 0.25, 0.5]);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn render() {
+    let mut rack = Rack::default();
+    let ramp = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    rack.mono(4f32);
+    let out = rack.render(1.0, 4.0, (ramp.tag(), 0));
+    assert_eq!(out, vec![0.0, 0.25, 0.5, 0.75]);
+    let (l, r) = rack.render_stereo(0.5, 4.0, (ramp.tag(), 0), (c.tag(), 0));
+    assert_eq!((l, r), (vec![0.0, 0.25], vec![0.5, 0.5]));
+}
+
+#[cfg(feature = "hound")]
+#[test]
+fn render_to_wav() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let path = std::env::temp_dir().join("oscen_render_to_wav.wav");
+    let path = path.to_str().unwrap();
+    rack.render_to_wav(0.5, 8.0, path, &[(c.tag(), 0), (c.tag(), 0)], 32)
+        .unwrap();
+    let mut reader = hound::WavReader::open(path).unwrap();
+    assert_eq!(reader.spec().channels, 2);
+    let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+    assert_eq!(samples, vec![0.5; 8]);
+    assert!(rack
+        .render_to_wav(0.5, 8.0, path, &[(c.tag(), 0)], 24)
+        .is_err());
+}
+
+#[test]
+fn nan_checks() {
+    let mut rack = Rack::default().with_nan_checks(true);
+    let zero = ConstBuilder::new(0.0.into()).rack(&mut rack);
+    let inv = InverseBuilder::new(zero.tag()).rack(&mut rack);
+    VcaBuilder::new(inv.tag()).rack(&mut rack);
+    assert_eq!(
+        rack.try_play(1.0).unwrap_err(),
+        RackError::NonFinite { tag: inv.tag() }
+    );
+    assert_eq!(rack.output(inv.tag(), 0), Some(0.0));
+    zero.set_value(&mut rack, 2.0.into());
+    assert_eq!(rack.try_play(1.0).unwrap()[0], 0.5);
+}
+
+#[test]
+fn connect() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let vca = VcaBuilder::new(c.tag()).rack(&mut rack);
+    rack.connect((c.tag(), 0), (vca.tag(), 0)).unwrap();
+    assert_eq!(rack.mono(1.0), 4.0);
+    assert_eq!(
+        rack.connect((c.tag(), 0), (vca.tag(), 0)),
+        Err(RackError::DuplicateConnection {
+            from: (c.tag(), 0),
+            to: (vca.tag(), 0)
+        })
+    );
+    assert_eq!(
+        rack.connect((Tag(7), 0), (vca.tag(), 0)),
+        Err(RackError::ModuleNotFound { tag: Tag(7) })
+    );
+    assert_eq!(
+        rack.connect((c.tag(), MAX_OUTPUTS), (vca.tag(), 0)),
+        Err(RackError::IndexOutOfRange {
+            tag: c.tag(),
+            index: MAX_OUTPUTS
+        })
+    );
+    assert_eq!(rack.validate(), Ok(()));
+    rack.disconnect((vca.tag(), 0)).unwrap();
+    assert_eq!(
+        rack.disconnect((vca.tag(), 0)),
+        Err(RackError::NotConnected { to: (vca.tag(), 0) })
+    );
+    assert_eq!(rack.mono(1.0), 0.0);
+}
+
+#[test]
+fn validate_cycle() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let a = VcaBuilder::new(c.tag()).rack(&mut rack);
+    let b = VcaBuilder::new(c.tag()).rack(&mut rack);
+    rack.connect((a.tag(), 0), (b.tag(), 0)).unwrap();
+    assert_eq!(rack.validate(), Ok(()));
+    rack.connect((b.tag(), 0), (a.tag(), 0)).unwrap();
+    let err = rack.validate().unwrap_err();
+    assert_eq!(
+        err,
+        RackError::CycleDetected {
+            path: vec![a.tag(), b.tag(), a.tag()]
+        }
+    );
+    assert_eq!(err.to_string(), "the connections form a cycle: 1 -> 2 -> 1");
+    rack.controls[(b.tag(), 0)] = Control::V(Tag(9), 0);
+    assert_eq!(
+        rack.validate(),
+        Err(RackError::ModuleNotFound { tag: Tag(9) })
+    );
+}
+
+#[test]
+fn connect_scaled() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    let vca = VcaBuilder::new(c.tag())
+        .level(c.tag() * 0.25)
+        .rack(&mut rack);
+    assert_eq!(rack.mono(1.0), 1.0);
+    rack.connect_scaled((c.tag(), 0), (vca.tag(), 0), 1.5)
+        .unwrap();
+    assert_eq!(rack.mono(1.0), 6.0);
+    assert!(rack
+        .connect_scaled((c.tag(), 0), (vca.tag(), 0), 1.5)
+        .is_err());
+    assert_eq!(
+        rack.connections().collect::<Vec<_>>(),
+        vec![Connection {
+            from: (c.tag(), 0),
+            to: (vca.tag(), 0)
+        }]
+    );
+}
+
+#[test]
+fn names() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc).rack(&mut rack);
+    let lpf = LpfBuilder::new(osc.tag()).rack(&mut rack);
+    assert_eq!(rack.control_by_name(lpf.tag(), "q"), Some((lpf.tag(), 1)));
+    assert_eq!(rack.control_by_name(lpf.tag(), "hz"), None);
+    let (tag, i) = rack.control_by_name(osc.tag(), "hz").unwrap();
+    rack.controls[(tag, i)] = 220.0.into();
+    assert_eq!(osc.hz(&rack), 220.0);
+    assert_eq!(rack.control_by_name(Tag(5), "hz"), None);
+}
+
+#[test]
+fn set_controls() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let vca = VcaBuilder::new(c.tag()).rack(&mut rack);
+    rack.set_controls(&[((c.tag(), 0), 2.0.into()), ((vca.tag(), 0), 3.0.into())]);
+    assert_eq!(rack.mono(1.0), 6.0);
+    rack.set_controls_ramped(&[((vca.tag(), 0), 1.0), ((c.tag(), 0), 1.0)], 2);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![3.0, 1.0, 1.0]);
+    rack.set_control_ramped((vca.tag(), 0), 5.0, 4);
+    let rs: Vec<f32> = (0..5).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![2.0, 3.0, 4.0, 5.0, 5.0]);
+}
+
+#[test]
+fn stereo() {
+    let mut rack = Rack::default();
+    let l = ConstBuilder::new(0.25.into()).rack(&mut rack);
+    let r = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    // A module with two outputs acts as a stereo source.
+    let pan = ModMatrixBuilder::new(vec![l.tag().into(), r.tag().into()], 2)
+        .depth(0, LEFT, 1.0.into())
+        .depth(1, RIGHT, 1.0.into())
+        .rack(&mut rack);
+    assert_eq!(rack.stereo(1.0), (0.25, 0.5));
+    assert_eq!(rack.stereo_output(pan.tag()), Some((0.25, 0.5)));
+    let (left, right) = rack.render_stereo(2.0, 1.0, pan.tag().left(), pan.tag().right());
+    assert_eq!((left, right), (vec![0.25; 2], vec![0.5; 2]));
+}
+
+#[test]
+fn connect_feedback() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let mix = VcaBuilder::new(c.tag()).rack(&mut rack);
+    let fb = VcaBuilder::new(mix.tag()).rack(&mut rack);
+    rack.connect((mix.tag(), 0), (fb.tag(), 0)).unwrap();
+    assert_eq!(
+        rack.connect_feedback((mix.tag(), 0), (fb.tag(), 1)),
+        Err(RackError::NotFeedback {
+            from: (mix.tag(), 0),
+            to: (fb.tag(), 1)
+        })
+    );
+    rack.connect((fb.tag(), 0), (mix.tag(), 0)).unwrap();
+    assert_eq!(
+        rack.validate(),
+        Err(RackError::CycleDetected {
+            path: vec![mix.tag(), fb.tag(), mix.tag()]
+        })
+    );
+    rack.disconnect((mix.tag(), 0)).unwrap();
+    rack.connect_feedback((fb.tag(), 0), (mix.tag(), 0))
+        .unwrap();
+    assert_eq!(rack.validate(), Ok(()));
+    // mix[n] = fb[n - 1] and fb[n] = mix[n] * mix[n].
+    rack.outputs[(fb.tag(), 0)] = 0.5;
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![0.25, 0.0625, 0.00390625]);
+}
+
+#[test]
+fn param_range() {
+    let range = ParamRange::new(20.0, 20000.0).log();
+    assert!((range.from_normalized(0.5) - 632.4555).abs() < 1e-2);
+    assert!((range.to_normalized(632.4555) - 0.5).abs() < 1e-6);
+    assert_eq!(range.clamp(5.0), 20.0);
+
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(|x, _| x).rack(&mut rack);
+    let lpf = LpfBuilder::new(osc.tag()).rack(&mut rack);
+    let cutoff = rack.control_by_name(lpf.tag(), "cutoff").unwrap();
+    rack.set_range(cutoff, range);
+    rack.set_param(cutoff, 1e6);
+    assert_eq!(lpf.cutoff(&rack), 20000.0);
+    rack.set_param_normalized(cutoff, 0.0);
+    assert_eq!(lpf.cutoff(&rack), 20.0);
+
+    rack.set_range(cutoff, ParamRange::new(0.0, 100.0).ramp(2));
+    rack.set_param(cutoff, 40.0);
+    rack.mono(1.0);
+    assert_eq!(lpf.cutoff(&rack), 30.0);
+    rack.mono(1.0);
+    assert_eq!(lpf.cutoff(&rack), 40.0);
+    assert_eq!(rack.range(cutoff).unwrap().ramp, 2);
+}
+
+#[test]
+fn reset_initial_state() {
+    let mut rack = Rack::default();
+    OscBuilder::new(|x, _| x).hz(1.0).phase(0.5).rack(&mut rack);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.5, 0.75, 0.0]);
+    rack.reset();
+    assert_eq!(rack.mono(4.0), 0.5);
+}
+
+#[test]
+fn processing_order() {
+    let mut rack = Rack::default();
+    let a = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let b = ConstBuilder::new(a.tag().into()).rack(&mut rack);
+    let c = ConstBuilder::new(b.tag().into()).rack(&mut rack);
+    assert_eq!(rack.processing_order(), &[a.tag(), b.tag(), c.tag()]);
+    rack.remove(b.tag());
+    assert_eq!(rack.processing_order(), &[a.tag(), c.tag()]);
+}
+
+#[test]
+fn schedule_control() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    rack.schedule_control((c.tag(), 0), 5.0.into(), 2);
+    rack.schedule_control((c.tag(), 0), 3.0.into(), 0);
+    let rs: Vec<f32> = (0..4).map(|_| rack.mono(1f32)).collect();
+    assert_eq!(rs, vec![3.0, 3.0, 5.0, 5.0]);
+}
+
+#[test]
+fn clone() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(|x, _| x).hz(0.25).rack(&mut rack);
+    rack.mono(1f32);
+    let mut other = rack.clone();
+    osc.set_hz(&mut other, 0.125.into());
+    assert_eq!(rack.mono(1f32), 0.25);
+    assert_eq!(other.mono(1f32), 0.25);
+    assert_eq!(rack.mono(1f32), 0.5);
+    assert_eq!(other.mono(1f32), 0.375);
+    assert_eq!(other.processing_order(), rack.processing_order());
+}
+
+#[test]
+fn clone_sub_rack() {
+    let mut inner = Rack::default();
+    let osc = OscBuilder::new(|x, _| x).hz(0.25).rack(&mut inner);
+    let mut rack = Rack::default();
+    let outputs = [(osc.tag(), 0)];
+    SubRackBuilder::new(inner, &[], &outputs).rack(&mut rack);
+    rack.mono(1f32);
+    // Each clone plays its own copy of the inner rack.
+    let mut other = rack.clone();
+    assert_eq!(rack.mono(1f32), 0.25);
+    assert_eq!(other.mono(1f32), 0.25);
+    assert_eq!(rack.mono(1f32), 0.5);
+    assert_eq!(other.mono(1f32), 0.5);
+}
+
+#[test]
+fn disconnect_all() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(sine_osc).hz(1.0).rack(&mut rack);
+    let osc = OscBuilder::new(sine_osc).rack(&mut rack);
+    rack.connect((lfo.tag(), 0), (osc.tag(), 0)).unwrap();
+    rack.connect((lfo.tag(), 0), (osc.tag(), 1)).unwrap();
+    rack.connect((osc.tag(), 0), (lfo.tag(), 1)).unwrap();
+    assert_eq!(rack.disconnect_all_from((lfo.tag(), 1)), 0);
+    assert_eq!(rack.disconnect_all_from((lfo.tag(), 0)), 2);
+    assert_eq!(rack.connections().count(), 1);
+    assert_eq!(rack.disconnect_all_into(lfo.tag()), 1);
+    assert_eq!(rack.connections().count(), 0);
+}
+
+#[test]
+fn connection_queries() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(sine_osc).hz(1.0).rack(&mut rack);
+    let osc = OscBuilder::new(sine_osc).rack(&mut rack);
+    rack.connect((lfo.tag(), 0), (osc.tag(), 0)).unwrap();
+    rack.connect_scaled((lfo.tag(), 0), (osc.tag(), 1), 0.5)
+        .unwrap();
+    assert!(rack.is_connected((lfo.tag(), 0), (osc.tag(), 1)));
+    assert!(!rack.is_connected((lfo.tag(), 0), (osc.tag(), 2)));
+    assert!(!rack.is_connected((osc.tag(), 0), (lfo.tag(), 0)));
+    assert_eq!(
+        rack.connections_from((lfo.tag(), 0)),
+        vec![(osc.tag(), 0), (osc.tag(), 1)]
+    );
+    assert!(rack.connections_from((osc.tag(), 0)).is_empty());
+}
+
+#[test]
+fn ramp_shapes() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(100.0.into()).rack(&mut rack);
+    rack.set_control_ramped_shaped((c.tag(), 0), 400.0, 2, RampShape::Exponential);
+    let rs: Vec<f32> = (0..3).map(|_| rack.mono(1.0)).collect();
+    assert_eq!(rs, vec![200.0, 400.0, 400.0]);
+    rack.set_control_ramped_shaped((c.tag(), 0), 100.0, 2, RampShape::Logarithmic);
+    assert_eq!(rack.mono(1.0), 300.0);
+    assert_eq!(rack.mono(1.0), 100.0);
+    // Exponential needs both ends to have the same sign.
+    rack.set_control_ramped_shaped((c.tag(), 0), -100.0, 2, RampShape::Exponential);
+    assert_eq!(rack.mono(1.0), 0.0);
+}
+
+#[test]
+fn bypass() {
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let lpf = LpfBuilder::new(osc.tag()).cut_off(0.1).rack(&mut rack);
+    rack.mono(4.0);
+    rack.set_bypassed(lpf.tag(), true);
+    assert!(rack.is_bypassed(lpf.tag()));
+    assert_eq!(rack.mono(4.0), 0.25);
+    rack.set_bypassed(osc.tag(), true);
+    assert_eq!(rack.mono(4.0), 0.0);
+    rack.set_bypassed(osc.tag(), false);
+    rack.set_bypassed(lpf.tag(), false);
+    assert!(rack.mono(4.0) < 0.25);
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn profile() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let partials = vec![1.0.into(); 24];
+    let additive = AdditiveOscBuilder::new(&partials).hz(20.0).rack(&mut rack);
+    for _ in 0..2000 {
+        rack.mono(44_100.0);
+    }
+    let profile = rack.profile();
+    assert_eq!(profile.len(), 2);
+    assert_eq!(profile[0].0, c.tag());
+    assert_eq!(profile[1].0, additive.tag());
+    assert!(profile[1].1 > profile[0].1);
+
+    rack.clear_profile();
+    assert!(rack.profile().iter().all(|(_, d)| d.is_zero()));
+}
+
+#[test]
+fn optimize() {
+    let mut rack = Rack::default();
+    let a = ConstantBuilder::new(2.0).rack(&mut rack);
+    let b = ConstBuilder::new(3.0.into()).rack(&mut rack);
+    let product = ProductBuilder::new(vec![a.tag(), b.tag()]).rack(&mut rack);
+    let lfo = OscBuilder::new(|x, _| x).hz(1.0).rack(&mut rack);
+    let mixer = MixerBuilder::new(vec![product.tag(), lfo.tag()]).rack(&mut rack);
+    let scaled = ConstBuilder::new(Control::V(product.tag(), 0)).rack(&mut rack);
+
+    let before: Vec<f32> = (0..4).map(|_| rack.play(4.0)[0]).collect();
+    rack.reset();
+    let folded = rack.optimize();
+    assert_eq!(folded, vec![a.tag(), b.tag(), product.tag(), scaled.tag()]);
+    assert_eq!(rack.processing_order(), &[lfo.tag(), mixer.tag()]);
+    let after: Vec<f32> = (0..4).map(|_| rack.play(4.0)[0]).collect();
+    assert_eq!(before, after);
+    assert_eq!(rack.outputs[(scaled.tag(), 0)], 6.0);
+
+    // Folded outputs survive a reset.
+    rack.reset();
+    assert_eq!(rack.mono(4.0), 6.0);
+
+    rack.unfold();
+    assert_eq!(rack.processing_order().len(), 6);
+    b.set_value(&mut rack, 4.0.into());
+    rack.mono(4.0);
+    assert_eq!(rack.outputs[(scaled.tag(), 0)], 8.0);
+}
+
+#[test]
+fn module_names() {
+    let mut rack = Rack::default();
+    let lfo = OscBuilder::new(sine_osc).hz(2.0).rack(&mut rack);
+    let osc = OscBuilder::new(saw_osc).rack(&mut rack);
+    rack.set_name(lfo.tag(), "lfo").unwrap();
+    rack.set_name(osc.tag(), "osc").unwrap();
+    assert_eq!(rack.tag_by_name("lfo"), Some(lfo.tag()));
+    assert_eq!(rack.name(osc.tag()), Some("osc"));
+    // Renaming drops the old name and a taken name moves to the new module.
+    rack.set_name(lfo.tag(), "mod").unwrap();
+    assert_eq!(rack.tag_by_name("lfo"), None);
+    rack.set_name(osc.tag(), "mod").unwrap();
+    assert_eq!(rack.tag_by_name("mod"), Some(osc.tag()));
+    assert_eq!(rack.name(lfo.tag()), None);
+    rack.remove(osc.tag());
+    assert_eq!(rack.tag_by_name("mod"), None);
+    assert_eq!(
+        rack.set_name(osc.tag(), "osc"),
+        Err(RackError::ModuleNotFound { tag: osc.tag() })
+    );
+}
+
+#[test]
+fn audio_rate_controls() {
+    // A control patched to another module follows it every sample.
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let lfo = OscBuilder::new(sine_osc).hz(11_025.0).rack(&mut rack);
+    VcaBuilder::new(c.tag()).level(lfo.tag()).rack(&mut rack);
+    let rs: Vec<f32> = (0..4).map(|_| rack.mono(44_100.0).round()).collect();
+    assert_eq!(rs, vec![0.0, 1.0, 0.0, -1.0]);
+}
+
+#[test]
+fn connect_many() {
+    let mut rack = Rack::default();
+    let c = ConstBuilder::new(0.5.into()).rack(&mut rack);
+    let voices: Vec<_> = (0..6)
+        .map(|_| VcaBuilder::new(c.tag()).rack(&mut rack))
+        .collect();
+    let gain = ConstBuilder::new(2.0.into()).rack(&mut rack);
+    rack.connect_many((gain.tag(), 0), voices[0..4].iter().map(|v| (v.tag(), 0)))
+        .unwrap();
+    assert_eq!(rack.connections_from((gain.tag(), 0)).len(), 4);
+    assert!(!rack.is_connected((gain.tag(), 0), (voices[4].tag(), 0)));
+    // One bad destination and none of them are patched.
+    let to = vec![(voices[4].tag(), 0), (voices[0].tag(), 0)];
+    assert_eq!(
+        rack.connect_many((gain.tag(), 0), to),
+        Err(RackError::DuplicateConnection {
+            from: (gain.tag(), 0),
+            to: (voices[0].tag(), 0)
+        })
+    );
+    assert!(!rack.is_connected((gain.tag(), 0), (voices[4].tag(), 0)));
+}
+
+#[test]
+fn sample_rate() {
+    let mut rack = Rack::default();
+    ConstBuilder::new(1.0.into()).rack(&mut rack);
+    assert_eq!(rack.sample_rate(), None);
+    rack.mono(44_100.0);
+    assert_eq!(rack.sample_rate(), Some(44_100.0));
+    rack.set_sample_rate(48_000.0);
+    assert_eq!(rack.sample_rate(), Some(48_000.0));
+}
+
+#[test]
+fn start_fade() {
+    let mut rack = Rack::default().with_start_fade(1.0);
+    let c = ConstBuilder::new(1.0.into()).rack(&mut rack);
+    let rs: Vec<f32> = (0..6).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(rs, vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0]);
+    // Modules still see the unfaded value.
+    assert_eq!(rack.outputs[(c.tag(), 0)], 1.0);
+    rack.reset();
+    assert_eq!(rack.mono(4.0), 0.0);
+    assert_eq!(rack.mono(4.0), 0.25);
+    // Rendering starts from silence too.
+    let rs = rack.render(1.5, 4.0, (c.tag(), 0));
+    assert_eq!(rs, vec![0.0, 0.25, 0.5, 0.75, 1.0, 1.0]);
+}
@@ -0,0 +1,31 @@
+use oscen::rack::*;
+use oscen::sampler::*;
+
+#[test]
+fn sample_player() {
+    let mut rack = Rack::default();
+    let sp = SamplePlayerBuilder::from_samples(vec![0.0, 1.0, 2.0, 3.0], 4.0).rack(&mut rack);
+    assert_eq!(rack.mono(4.0), 0.0);
+    sp.trigger(&mut rack);
+    let out: Vec<f32> = (0..5).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(out, vec![0.0, 1.0, 2.0, 3.0, 0.0]);
+    assert!(!sp.playing(&rack));
+
+    // Half speed at double the source rate interpolates between samples.
+    sp.trigger(&mut rack);
+    let out: Vec<f32> = (0..6).map(|_| rack.mono(16.0)).collect();
+    assert_eq!(out[4..], [1.0, 1.25]);
+}
+
+#[test]
+fn sample_player_loop() {
+    let mut rack = Rack::default();
+    let sp = SamplePlayerBuilder::from_samples(vec![0.0, 1.0, 2.0, 3.0], 4.0)
+        .loop_mode(LoopMode::Forward)
+        .start(0.5)
+        .rack(&mut rack);
+    sp.trigger(&mut rack);
+    let out: Vec<f32> = (0..5).map(|_| rack.mono(4.0)).collect();
+    assert_eq!(out, vec![2.0, 3.0, 2.0, 3.0, 2.0]);
+    assert!(sp.playing(&rack));
+}
@@ -0,0 +1,45 @@
+use oscen::oscillators::*;
+use oscen::rack::*;
+use oscen::shaping::*;
+use oscen::testing::partial;
+
+#[test]
+fn oversample() {
+    let render = |factor: Option<usize>| {
+        let mut rack = Rack::default();
+        let osc = OscBuilder::new(sine_osc).hz(5_000.0).rack(&mut rack);
+        match factor {
+            Some(f) => {
+                OversampleBuilder::new(osc.tag())
+                    .factor(f)
+                    .rack(&mut rack, |rack, input| TanhBuilder::new(input).rack(rack));
+            }
+            None => {
+                TanhBuilder::new(osc.tag()).rack(&mut rack);
+            }
+        }
+        (0..44_100)
+            .map(|_| rack.mono(44_100.0))
+            .collect::<Vec<f32>>()
+    };
+    let plain = render(None);
+    let oversampled = render(Some(4));
+    // The fundamental is kept and the 7th harmonic, at 35 kHz, no longer
+    // folds back to 9.1 kHz.
+    let fundamental = partial(&oversampled, 5_000.0, 44_100.0);
+    assert!((fundamental / partial(&plain, 5_000.0, 44_100.0) - 1.0).abs() < 0.1);
+    assert!(partial(&oversampled, 9_100.0, 44_100.0) < 0.1 * partial(&plain, 9_100.0, 44_100.0));
+}
+
+#[test]
+fn oversample_passthrough() {
+    // With no inner modules the input only goes through the two filters.
+    let mut rack = Rack::default();
+    let osc = OscBuilder::new(sine_osc).hz(100.0).rack(&mut rack);
+    let (os, _) = OversampleBuilder::new(osc.tag())
+        .factor(2)
+        .rack(&mut rack, |_, _| ());
+    assert!(os.inner().is_empty());
+    let xs: Vec<f32> = (0..44_100).map(|_| rack.mono(44_100.0)).collect();
+    assert!((partial(&xs, 100.0, 44_100.0) - 1.0).abs() < 1e-2);
+}